@@ -0,0 +1,58 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use sp_domain::*;
+
+// the store only depends on sp_domain, pull it in directly instead of
+// going through the binary.
+#[path = "../src/state_store.rs"]
+#[allow(dead_code)]
+mod state_store;
+use state_store::StateStore;
+
+const VARIABLES: usize = 10_000;
+
+fn values(step: i32) -> Vec<(SPPath, SPValue)> {
+    (0..VARIABLES)
+        .map(|i| {
+            let path = SPPath::from_slice(&["resource", &format!("r{}", i % 100), &format!("v{}", i)]);
+            // only every hundredth value moves between steps.
+            let value = if i % 100 == 0 { step } else { 0 };
+            (path, SPValue::Int32(value))
+        })
+        .collect()
+}
+
+fn bench_state_store(c: &mut Criterion) {
+    let initial = values(0);
+    let state = SPState::new_from_values(&values(1));
+
+    c.bench_function("insert 10k variables", |b| {
+        b.iter_batched(|| initial.clone(),
+                       |vs| {
+                           let mut store: StateStore<(SPPath, SPValue)> = StateStore::default();
+                           store.update(vs);
+                           store
+                       },
+                       BatchSize::LargeInput)
+    });
+
+    let mut store: StateStore<(SPPath, SPValue)> = StateStore::default();
+    store.update(initial.clone());
+    c.bench_function("update 10k variables, 100 changed", |b| {
+        let mut step = 0;
+        b.iter_batched(|| { step += 1; values(step) },
+                       |vs| store.update(vs).len(),
+                       BatchSize::LargeInput)
+    });
+
+    c.bench_function("update from a 10k variable SPState", |b| {
+        b.iter(|| store.update_from(black_box(&state)).len())
+    });
+
+    let lookups: Vec<SPPath> = initial.iter().step_by(37).map(|(p, _)| p.clone()).collect();
+    c.bench_function("look up 270 paths", |b| {
+        b.iter(|| lookups.iter().filter(|p| store.value(p).is_some()).count())
+    });
+}
+
+criterion_group!(benches, bench_state_store);
+criterion_main!(benches);
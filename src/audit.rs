@@ -0,0 +1,177 @@
+use chrono::Local;
+use iced::{button, scrollable, Button, Column, Element, Length, Row, Scrollable, Text};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+use crate::{Error, Message};
+
+/// One command sent to the runner. Written as a line when it is sent and
+/// again, with `answered` set, when the runner answers or the call fails.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// rfc 3339 local time
+    pub time: String,
+    /// the ui message that caused the command
+    pub cause: String,
+    /// `set_state` or `set_model`
+    pub command: String,
+    /// exactly what we sent
+    pub sent: String,
+    /// false until the call has finished one way or the other
+    pub answered: bool,
+    /// what the runner answered, if it answered at all
+    pub response: Option<String>,
+    pub ok: bool,
+    pub error: Option<String>,
+}
+
+/// Appends every command to one json lines file per session and keeps
+/// the entries around for the audit view. Shared with the command futures.
+pub struct AuditLog {
+    pub path: String,
+    file: Option<std::fs::File>,
+    entries: Vec<AuditEntry>,
+    /// failed writes the ui has not reported yet
+    write_errors: Vec<Error>,
+}
+
+impl AuditLog {
+    /// Opens a new file in `dir`. If that fails we still keep the
+    /// entries in memory so nothing is lost from the view.
+    pub fn create(dir: &str) -> (AuditLog, Option<Error>) {
+        let name = format!("sp_audit_{}.jsonl", Local::now().format("%Y%m%d_%H%M%S"));
+        let path = Path::new(dir).join(name).to_string_lossy().to_string();
+        let file = std::fs::create_dir_all(dir)
+            .and_then(|_| std::fs::OpenOptions::new().create(true).append(true).open(&path));
+        let (file, error) = match file {
+            Ok(f) => (Some(f), None),
+            Err(e) => (None, Some(Error::io(&format!("could not open audit log {}", path), e))),
+        };
+        (AuditLog { path, file, entries: vec![], write_errors: vec![] }, error)
+    }
+
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// Log a command before it goes out, so a call that hangs or takes
+    /// us down is still on record. Returns the entry to answer later.
+    pub fn sent(&mut self, cause: &str, command: &str, sent: &str) -> usize {
+        let entry = AuditEntry {
+            time: Local::now().to_rfc3339(),
+            cause: cause.to_string(),
+            command: command.to_string(),
+            sent: sent.to_string(),
+            answered: false,
+            response: None,
+            ok: false,
+            error: None,
+        };
+        self.write(&entry);
+        self.entries.push(entry);
+        self.entries.len() - 1
+    }
+
+    pub fn answered(&mut self, index: usize, result: &Result<String, Error>) {
+        let entry = match self.entries.get_mut(index) {
+            Some(entry) => entry,
+            None => return,
+        };
+        entry.time = Local::now().to_rfc3339();
+        entry.answered = true;
+        entry.response = match result {
+            Ok(r) => Some(r.clone()),
+            Err(Error::Rejected { response, .. }) => Some(response.clone()),
+            Err(_) => None,
+        };
+        entry.ok = result.is_ok();
+        entry.error = result.as_ref().err().map(|e| e.to_string());
+        let entry = entry.clone();
+        self.write(&entry);
+    }
+
+    /// Failed writes since the last call, for the ui to report.
+    pub fn take_write_errors(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.write_errors)
+    }
+
+    fn write(&mut self, entry: &AuditEntry) {
+        if let Some(file) = self.file.as_mut() {
+            let written = serde_json::to_string(entry)
+                .map_err(Error::from)
+                .and_then(|line| writeln!(file, "{}", line).map_err(Error::from));
+            if let Err(e) = written {
+                self.write_errors.push(Error::Io {
+                    what: format!("could not write audit log {}", self.path),
+                    message: e.to_string(),
+                });
+            }
+        }
+    }
+}
+
+/// Widget state for browsing the audit log.
+#[derive(Debug, Default)]
+pub struct AuditView {
+    expanded: HashSet<usize>,
+    buttons: HashMap<usize, button::State>,
+    scroll: scrollable::State,
+}
+
+impl AuditView {
+    pub fn toggle(&mut self, index: usize) {
+        if !self.expanded.remove(&index) {
+            self.expanded.insert(index);
+        }
+    }
+
+    pub fn view<'a>(&'a mut self, log: &AuditLog) -> Element<'a, Message> {
+        let entries = log.entries();
+        for i in 0..entries.len() {
+            self.buttons.entry(i).or_default();
+        }
+        let mut buttons: HashMap<&usize, &mut button::State> = self.buttons.iter_mut().collect();
+        let expanded = &self.expanded;
+
+        let rows = entries
+            .iter()
+            .enumerate()
+            .rev()
+            .fold(Column::new().spacing(5), |col, (i, e)| {
+                let (result, color) = if !e.answered {
+                    ("waiting for an answer".to_string(), [0.5, 0.5, 0.5])
+                } else if e.ok {
+                    ("ok".to_string(), [0.0, 0.5, 0.0])
+                } else {
+                    (e.error.clone().unwrap_or_default(), [0.8, 0.1, 0.1])
+                };
+                let time = e.time.get(11..19).unwrap_or(&e.time).to_string();
+                let title = Row::new()
+                    .spacing(10)
+                    .push(Text::new(time).size(14).color([0.5, 0.5, 0.5]))
+                    .push(Text::new(&e.command).size(14))
+                    .push(Text::new(&e.cause).size(14))
+                    .push(Text::new(result).size(14).color(color));
+                let col = col.push(Button::new(buttons.remove(&i).unwrap(), title)
+                                   .on_press(Message::ToggleAuditEntry(i)));
+                if expanded.contains(&i) {
+                    let response = e.response.clone().unwrap_or("(no answer)".to_string());
+                    col.push(Column::new()
+                             .padding(5)
+                             .push(Text::new(format!("sent: {}", e.sent)).size(12))
+                             .push(Text::new(format!("response: {}", response)).size(12)))
+                } else {
+                    col
+                }
+            });
+
+        Column::new()
+            .spacing(10)
+            .push(Text::new(format!("{} commands, logged to {}", entries.len(), log.path)).size(16))
+            .push(Scrollable::new(&mut self.scroll)
+                  .height(Length::Fill)
+                  .push(rows))
+            .into()
+    }
+}
@@ -0,0 +1,365 @@
+use futures::future::{self, BoxFuture, FutureExt};
+use futures::stream::StreamExt;
+use r2r;
+use serde::Deserialize;
+use sp_domain::*;
+use sp_formal::CompiledModel;
+use std::sync::{Arc, Mutex};
+use crate::Error;
+use crate::config::Config;
+use crate::state_channel::{state_channel, StateReceiver, StateSender};
+use crate::status::ServiceStatus;
+use std::time::Duration;
+
+/// What comes out of the state stream.
+#[derive(Debug, Clone)]
+pub enum StateUpdate {
+    State(SPState),
+    /// A message we could not parse, with the start of its payload.
+    ParseError(String, String),
+}
+
+/// Everything the viewer needs from the sp runner. The ros
+/// implementation talks to the runner services, the mock keeps
+/// everything in memory so the ui can run without a ros graph.
+pub trait Backend: Send + Sync {
+    fn get_model(&self) -> BoxFuture<'static, Result<CompiledModel, Error>>;
+
+    /// `set_model` and `set_state` take the exact json to send and give
+    /// back the runner's answer.
+    fn set_model(&self, json: String) -> BoxFuture<'static, Result<String, Error>>;
+    fn set_state(&self, json: String) -> BoxFuture<'static, Result<String, Error>>;
+    fn service_status(&self) -> BoxFuture<'static, ServiceStatus>;
+
+    /// The state stream can only be taken once.
+    fn take_state_receiver(&self) -> Option<StateReceiver>;
+}
+
+type JsonClient = Arc<Mutex<r2r::Client<r2r::sp_msgs::srv::Json::Service>>>;
+
+async fn call(client: JsonClient, service: String, timeout: Duration, json: String) -> Result<String, Error> {
+    let req_msg = r2r::sp_msgs::srv::Json::Request { json };
+    let req = client.lock().unwrap().request(&req_msg).map_err(|e| Error::ServiceError {
+        service: service.clone(),
+        message: e.to_string(),
+    })?;
+    match tokio::time::timeout(timeout, req).await {
+        Ok(Ok(resp)) => Ok(resp.json),
+        Ok(Err(e)) => Err(Error::ServiceError {
+            service,
+            message: e.to_string(),
+        }),
+        Err(_) => Err(Error::ServiceTimeout { service, timeout }),
+    }
+}
+
+/// Turn a refusal from the runner into `Error::Rejected`. The runner's
+/// answer format is not pinned down anywhere we can check from here, so
+/// this only recognizes an object with an `error` key or with
+/// `"success": false` (and an optional `message`). Anything else, empty,
+/// plain text or other json, counts as accepted and is passed on as is.
+fn check_response(service: &str, json: String) -> Result<String, Error> {
+    let rejected = |message: String| Err(Error::Rejected {
+        service: service.to_string(),
+        message,
+        response: json.clone(),
+    });
+    match serde_json::from_str::<serde_json::Value>(&json) {
+        Ok(serde_json::Value::Object(o)) => {
+            // strings without their json quotes, anything else as json.
+            let text = |v: &serde_json::Value| v.as_str().map(|s| s.to_string()).unwrap_or(v.to_string());
+            if let Some(e) = o.get("error") {
+                rejected(text(e))
+            } else if o.get("success") == Some(&serde_json::Value::Bool(false)) {
+                rejected(o.get("message").map(text).unwrap_or("no reason given".into()))
+            } else {
+                Ok(json)
+            }
+        },
+        _ => Ok(json),
+    }
+}
+
+async fn is_available(client: JsonClient) -> bool {
+    let waiting = r2r::Node::is_available(&*client.lock().unwrap());
+    match waiting {
+        Ok(waiting) => matches!(tokio::time::timeout(Duration::from_millis(200), waiting).await,
+                                Ok(Ok(()))),
+        Err(_) => false,
+    }
+}
+
+pub struct RosBackend {
+    get_model_client: JsonClient,
+    set_model_client: JsonClient,
+    set_state_client: JsonClient,
+    new_state_receiver: Mutex<Option<StateReceiver>>,
+    get_model_service: String,
+    set_model_service: String,
+    set_state_service: String,
+    get_model_timeout: Duration,
+    set_model_timeout: Duration,
+    set_state_timeout: Duration,
+}
+
+impl RosBackend {
+    pub fn new(config: &Config) -> Self {
+        let ctx = r2r::Context::create().expect("could not create context");
+        let mut node = r2r::Node::create(ctx, &config.node_name, &config.namespace).expect("...");
+        let get_model_service = config.resolve(&config.get_model_service);
+        let set_model_service = config.resolve(&config.set_model_service);
+        let set_state_service = config.resolve(&config.set_state_service);
+        let get_model_client = Arc::new(Mutex::new(
+            node.create_client::<r2r::sp_msgs::srv::Json::Service>(&get_model_service)
+                .expect("could not create client"),
+        ));
+        let set_model_client = Arc::new(Mutex::new(
+            node.create_client::<r2r::sp_msgs::srv::Json::Service>(&set_model_service)
+                .expect("could not create client"),
+        ));
+        let set_state_client = Arc::new(Mutex::new(
+            node.create_client::<r2r::sp_msgs::srv::Json::Service>(&set_state_service)
+                .expect("could not create client"),
+        ));
+        let sub = node
+            .subscribe::<r2r::std_msgs::msg::String>(&config.resolve(&config.state_topic),
+                                                     r2r::QosProfile::default())
+            .expect("could not subscribe");
+
+        let _handle = std::thread::spawn(move || loop {
+            node.spin_once(std::time::Duration::from_millis(100));
+        });
+
+        let (sender, receiver) = state_channel(config.state_channel, config.state_queue);
+        let _sub = tokio::spawn(async move {
+            sub.for_each(|msg| {
+                let update = match serde_json::from_str::<SPStateJson>(&msg.data) {
+                    Ok(s) => StateUpdate::State(s.to_state()),
+                    Err(e) => StateUpdate::ParseError(e.to_string(), msg.data.chars().take(80).collect()),
+                };
+                sender.send(update);
+                future::ready(())
+            })
+            .await;
+        });
+
+        RosBackend {
+            get_model_client,
+            set_model_client,
+            set_state_client,
+            new_state_receiver: Mutex::new(Some(receiver)),
+            get_model_service,
+            set_model_service,
+            set_state_service,
+            get_model_timeout: Duration::from_millis(config.get_model_timeout_ms),
+            set_model_timeout: Duration::from_millis(config.set_model_timeout_ms),
+            set_state_timeout: Duration::from_millis(config.set_state_timeout_ms),
+        }
+    }
+}
+
+impl Backend for RosBackend {
+    fn get_model(&self) -> BoxFuture<'static, Result<CompiledModel, Error>> {
+        let client = self.get_model_client.clone();
+        let service = self.get_model_service.clone();
+        let timeout = self.get_model_timeout;
+        async move {
+            let json = call(client, service.clone(), timeout, String::new()).await?;
+
+            #[derive(Debug, PartialEq, Clone, Default, Deserialize)]
+            pub struct RunnerModel {
+                pub compiled_model: CompiledModel,
+                pub changes: Option<sp_domain::Model>,
+            }
+
+            let rm: RunnerModel = Error::parse_json(&format!("response from {}", service), &json)?;
+            Ok(rm.compiled_model)
+        }.boxed()
+    }
+
+    fn set_model(&self, json: String) -> BoxFuture<'static, Result<String, Error>> {
+        let client = self.set_model_client.clone();
+        let service = self.set_model_service.clone();
+        let timeout = self.set_model_timeout;
+        async move {
+            let response = call(client, service.clone(), timeout, json).await?;
+            check_response(&service, response)
+        }.boxed()
+    }
+
+    fn set_state(&self, json: String) -> BoxFuture<'static, Result<String, Error>> {
+        let client = self.set_state_client.clone();
+        let service = self.set_state_service.clone();
+        let timeout = self.set_state_timeout;
+        async move {
+            let response = call(client, service.clone(), timeout, json).await?;
+            check_response(&service, response)
+        }.boxed()
+    }
+
+    fn service_status(&self) -> BoxFuture<'static, ServiceStatus> {
+        let get_model = is_available(self.get_model_client.clone());
+        let set_model = is_available(self.set_model_client.clone());
+        let set_state = is_available(self.set_state_client.clone());
+        async move {
+            let (get_model, set_model, set_state) = future::join3(get_model, set_model, set_state).await;
+            ServiceStatus {
+                get_model,
+                set_model,
+                set_state,
+            }
+        }.boxed()
+    }
+
+    fn take_state_receiver(&self) -> Option<StateReceiver> {
+        self.new_state_receiver.lock().unwrap().take()
+    }
+}
+
+/// In-process stand-in for the runner. Serves a compiled model loaded
+/// from a json file and echoes state changes back on the state stream.
+pub struct MockBackend {
+    compiled_model: Arc<Mutex<CompiledModel>>,
+    state: Arc<Mutex<SPState>>,
+    new_state_sender: StateSender,
+    new_state_receiver: Mutex<Option<StateReceiver>>,
+}
+
+impl MockBackend {
+    pub fn from_file(path: &str, config: &Config) -> Result<Self, Error> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| Error::io(&format!("could not read {}", path), e))?;
+        let compiled_model: CompiledModel = Error::parse_json(&format!("compiled model in {}", path), &json)?;
+        Ok(MockBackend::new(compiled_model, config))
+    }
+
+    pub fn new(compiled_model: CompiledModel, config: &Config) -> Self {
+        // start every variable in the first value of its domain.
+        let initial: Vec<_> = compiled_model.model.resources
+            .iter()
+            .flat_map(|r| r.variables.iter())
+            .map(|v| {
+                let value = v.domain.first().cloned().unwrap_or(SPValue::Unknown);
+                (v.path.clone(), value)
+            })
+            .collect();
+        let state = Arc::new(Mutex::new(SPState::new_from_values(&initial)));
+
+        // publish the state periodically like the runner does.
+        let (sender, receiver) = state_channel(config.state_channel, config.state_queue);
+        let task_state = state.clone();
+        let task_sender = sender.clone();
+        let _pub = tokio::spawn(async move {
+            loop {
+                let s = task_state.lock().unwrap().clone();
+                task_sender.send(StateUpdate::State(s));
+                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            }
+        });
+
+        MockBackend {
+            compiled_model: Arc::new(Mutex::new(compiled_model)),
+            state,
+            new_state_sender: sender,
+            new_state_receiver: Mutex::new(Some(receiver)),
+        }
+    }
+}
+
+impl Backend for MockBackend {
+    fn get_model(&self) -> BoxFuture<'static, Result<CompiledModel, Error>> {
+        let compiled_model = self.compiled_model.lock().unwrap().clone();
+        future::ready(Ok(compiled_model)).boxed()
+    }
+
+    fn set_model(&self, json: String) -> BoxFuture<'static, Result<String, Error>> {
+        // the runner only picks up new intentions from set_model.
+        let result = Error::parse_json::<Model>("model change", &json).map(|model| {
+            self.compiled_model.lock().unwrap().model.intentions.extend(model.intentions);
+            String::new()
+        });
+        future::ready(result).boxed()
+    }
+
+    fn set_state(&self, json: String) -> BoxFuture<'static, Result<String, Error>> {
+        let result = Error::parse_json::<SPStateJson>("state change", &json).map(|s| {
+            let changes = s.to_state();
+            let mut state = self.state.lock().unwrap();
+            let mut values: Vec<_> = state.projection().state
+                .iter()
+                .map(|(k, v)| ((*k).clone(), v.value().clone()))
+                .collect();
+            for (k, v) in changes.projection().state {
+                match values.iter_mut().find(|(p, _)| p == k) {
+                    Some(ref mut pv) => pv.1 = v.value().clone(),
+                    None => values.push((k.clone(), v.value().clone())),
+                }
+            }
+            *state = SPState::new_from_values(&values);
+            self.new_state_sender.send(StateUpdate::State(state.clone()));
+            String::new()
+        });
+        future::ready(result).boxed()
+    }
+
+    fn service_status(&self) -> BoxFuture<'static, ServiceStatus> {
+        future::ready(ServiceStatus {
+            get_model: true,
+            set_model: true,
+            set_state: true,
+        }).boxed()
+    }
+
+    fn take_state_receiver(&self) -> Option<StateReceiver> {
+        self.new_state_receiver.lock().unwrap().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected(result: Result<String, Error>) -> String {
+        match result {
+            Err(Error::Rejected { message, .. }) => message,
+            other => panic!("expected a rejection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn empty_response_is_ok() {
+        assert_eq!(check_response("/sp/set_state", String::new()).unwrap(), "");
+    }
+
+    #[test]
+    fn error_object_is_rejected() {
+        let json = r#"{"error": "no such variable"}"#.to_string();
+        assert_eq!(rejected(check_response("/sp/set_state", json)), "no such variable");
+    }
+
+    #[test]
+    fn success_false_is_rejected() {
+        let json = r#"{"success": false, "message": "busy"}"#.to_string();
+        assert_eq!(rejected(check_response("/sp/set_model", json)), "busy");
+        let json = r#"{"success": false}"#.to_string();
+        assert_eq!(rejected(check_response("/sp/set_model", json)), "no reason given");
+        let json = r#"{"success": true}"#.to_string();
+        assert!(check_response("/sp/set_model", json).is_ok());
+    }
+
+    #[test]
+    fn rejections_keep_the_full_response() {
+        let json = r#"{"error": "x"}"#.to_string();
+        match check_response("/sp/set_state", json.clone()) {
+            Err(Error::Rejected { service, response, .. }) => {
+                assert_eq!((service.as_str(), response), ("/sp/set_state", json));
+            },
+            other => panic!("expected a rejection, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn non_json_body_is_passed_on() {
+        assert_eq!(check_response("/sp/set_state", "ok".to_string()).unwrap(), "ok");
+    }
+}
@@ -1,19 +1,22 @@
 use iced::{
-    button, scrollable, text_input, Scrollable, Container, container,
+    button, scrollable, text_input, pick_list, Scrollable, Container, container,
     Color,
-    Alignment, Button, Column, Element, Length, Row, Text, TextInput
+    Alignment, Button, Column, Element, Length, PickList, Row, Space, Text, TextInput, Toggler
 };
+use std::collections::{HashMap, HashSet};
 use iced::alignment::{Horizontal, Vertical};
 use sp_domain::*;
 use sp_formal::CompiledModel;
 use fuzzy_matcher::FuzzyMatcher;
 use fuzzy_matcher::skim::SkimMatcherV2;
 use crate::{Message, BufferLocationType};
+use crate::state_tree::{tree_rows, TreeRow};
+use crate::guards::{view_operation_detail, view_transition_detail};
+use crate::goal_layout::GoalLayout;
+use crate::model_browser::ModelBrowser;
+use crate::state_store::{StateEntry, StateStore};
 
-// SIDExSIDE buffer view for goal generation.
-static NUM_BUFFERS_SIDE: usize = 2;
-
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum NotificationType {
     Happy,
     Neutral,
@@ -22,14 +25,16 @@ pub enum NotificationType {
 
 #[derive(Debug, Clone)]
 pub struct Notification {
+    pub id: u64,
     pub message: String,
     pub type_: NotificationType,
     pub close_button_state: button::State,
 }
 
 impl Notification {
-    pub fn new(message: String, type_: NotificationType) -> Self {
+    pub fn new(id: u64, message: String, type_: NotificationType) -> Self {
         Notification {
+            id,
             message,
             type_,
             close_button_state: button::State::new(),
@@ -38,7 +43,7 @@ impl Notification {
 
     pub fn view(&mut self) -> Container<Message> {
         let close_button = Button::new(&mut self.close_button_state, Text::new("X"))
-            .on_press(Message::ClearNotification)
+            .on_press(Message::ClearNotification(self.id))
             .style(NotificationStyle {
                 type_: self.type_,
             });
@@ -177,6 +182,7 @@ impl button::StyleSheet for SadNotificationStyle {
 #[derive(Debug, Clone)]
 pub struct OperationInfo {
     pub op: Operation,
+    pub select_button_state: button::State,
     pub start_button_state: button::State,
     pub reset_button_state: button::State,
 }
@@ -195,85 +201,149 @@ pub struct StateInfo {
     pub value: SPValue,
     pub new_value: String,
     pub new_value_state: text_input::State,
+    pub pick_state: pick_list::State<String>,
+    pub edit_error: Option<String>,
+}
+
+impl StateEntry for StateInfo {
+    fn new(path: SPPath, value: SPValue) -> Self {
+        StateInfo {
+            path,
+            value,
+            ..StateInfo::default()
+        }
+    }
+
+    fn path(&self) -> &SPPath {
+        &self.path
+    }
+
+    fn value(&self) -> &SPValue {
+        &self.value
+    }
+
+    fn set_value(&mut self, value: SPValue) {
+        self.value = value;
+    }
+}
+
+/// Which input to show for a state variable, based on its declared
+/// type and domain in the model.
+#[derive(Debug, Clone)]
+pub enum ValueEditor {
+    Toggle,
+    Pick(Vec<String>),
+    Number(SPValueType),
+    Text,
+    /// nothing to edit with, for --read-only.
+    ReadOnly,
+}
+
+impl ValueEditor {
+    pub fn for_variable(v: Option<&Variable>, current: &SPValue) -> Self {
+        let (value_type, domain) = match v {
+            Some(v) => (v.value_type, v.domain.as_slice()),
+            None => (current.has_type(), &[][..]),
+        };
+        match value_type {
+            SPValueType::Bool => ValueEditor::Toggle,
+            // picks go through parse_value too, so paths, arrays etc. cannot be edited at all.
+            SPValueType::Int32 | SPValueType::Float32 | SPValueType::String if !domain.is_empty() =>
+                ValueEditor::Pick(domain.iter().map(|d| d.to_string()).collect()),
+            SPValueType::Int32 | SPValueType::Float32 => ValueEditor::Number(value_type),
+            SPValueType::String => ValueEditor::Text,
+            _ => ValueEditor::ReadOnly,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct BufferLocation {
-    pub value: bool,
+    /// index into the values of the cell
+    pub value: usize,
     pub button: button::State,
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct SPModelInfo {
     pub compiled_model: CompiledModel,
-    pub state: Vec<StateInfo>,
+    pub state: StateStore<StateInfo>,
     pub operations: Vec<OperationInfo>,
     pub intentions: Vec<IntentionInfo>,
+    pub goal_layout: GoalLayout,
     pub buffers_locations: Vec<BufferLocation>,
     pub estimated_locations: Vec<BufferLocation>,
     pub order_button: button::State,
     pub update_button: button::State,
+    pub apply_edits_button: button::State,
+    pub expanded: HashSet<SPPath>,
+    pub group_buttons: HashMap<SPPath, button::State>,
+    pub selected_operation: Option<SPPath>,
+    pub close_detail_button: button::State,
+    pub expanded_transitions: HashSet<String>,
+    pub tplan_buttons: HashMap<String, button::State>,
+    pub browser: ModelBrowser,
 }
 
 impl OperationInfo {
-    pub(crate) fn view(&mut self, state_value: &str) -> Element<Message> {
-        Row::new()
+    pub(crate) fn view(&mut self, state_value: &str, highlight: &[usize], read_only: bool) -> Element<Message> {
+        let row = Row::new()
             .align_items(Alignment::Center)
             .spacing(20)
             .push(
-                Text::new(self.op.path().leaf())
-                    .size(30)
-                    .width(Length::Fill),
+                Button::new(&mut self.select_button_state,
+                            Text::new(self.op.path().leaf()).size(30))
+                    .width(Length::Fill)
+                    .on_press(Message::SelectOperation(Some(self.op.path.clone()))),
             )
-            .push(
-                Text::new(self.op.path().to_string())
-                    .size(10)
-                    .color([0.5, 0.5, 0.5]),
-            )
-            .push(Text::new(state_value).size(20).color([0.2, 0.2, 0.2]))
+            .push(highlighted(&self.op.path().to_string(), highlight, 10, [0.5, 0.5, 0.5]))
+            .push(Text::new(state_value).size(20).color([0.2, 0.2, 0.2]));
+        if read_only {
+            return row.into();
+        }
+        row
             .push(
                 Button::new(&mut self.start_button_state, Text::new("Force start").size(10))
                     .padding(10)
-                    .on_press(Message::ResetOperation(self.op.path.clone(), "e".to_spvalue())),
+                    .on_press(Message::AskResetOperation(self.op.path.clone(), "e".to_spvalue())),
             )
             .push(
                 Button::new(&mut self.reset_button_state, Text::new("Reset").size(10))
                     .padding(10)
-                    .on_press(Message::ResetOperation(self.op.path.clone(), "i".to_spvalue())),
+                    .on_press(Message::AskResetOperation(self.op.path.clone(), "i".to_spvalue())),
             )
             .into()
     }
 }
 
 impl IntentionInfo {
-    pub(crate) fn view(&mut self, state_value: &str) -> Element<Message> {
-        Row::new()
+    pub(crate) fn view(&mut self, state_value: &str, highlight: &[usize], read_only: bool) -> Element<Message> {
+        let row = Row::new()
             .align_items(Alignment::Center)
             .spacing(20)
             .push(Text::new(self.i.path().leaf()).size(30).width(Length::Fill))
-            .push(
-                Text::new(self.i.path().to_string())
-                    .size(10)
-                    .color([0.5, 0.5, 0.5]),
-            )
-            .push(Text::new(state_value).size(20).color([0.2, 0.2, 0.2]))
+            .push(highlighted(&self.i.path().to_string(), highlight, 10, [0.5, 0.5, 0.5]))
+            .push(Text::new(state_value).size(20).color([0.2, 0.2, 0.2]));
+        if read_only {
+            return row.into();
+        }
+        row
             .push(Button::new(&mut self.stop_button_state, Text::new("Stop").size(10))
                               .padding(10)
-                              .on_press(Message::ResetOperation(self.i.path.clone(), "X".to_spvalue())))
+                              .on_press(Message::AskResetOperation(self.i.path.clone(), "X".to_spvalue())))
             .push(Button::new(&mut self.start_button_state, Text::new("Force start").size(10))
                               .padding(10)
-                              .on_press(Message::ResetOperation(self.i.path.clone(), "e".to_spvalue())))
+                              .on_press(Message::AskResetOperation(self.i.path.clone(), "e".to_spvalue())))
             .push(Button::new(&mut self.reset_button_state, Text::new("Reset").size(10))
                               .padding(10)
-                              .on_press(Message::ResetOperation(self.i.path.clone(), "i".to_spvalue())))
+                              .on_press(Message::AskResetOperation(self.i.path.clone(), "i".to_spvalue())))
             .into()
     }
 }
 
 impl SPModelInfo {
     pub(crate) fn get_spstate(&self) -> SPState {
-        let state: Vec<_> = self.state.iter().map(|si| (si.path.clone(), si.value.clone())).collect();
-        SPState::new_from_values(&state)
+        self.state.to_state()
     }
 
     pub(crate) fn from(compiled_model: CompiledModel) -> SPModelInfo {
@@ -283,6 +353,7 @@ impl SPModelInfo {
             .iter()
             .map(|o| OperationInfo {
                 op: o.clone(),
+                select_button_state: button::State::new(),
                 start_button_state: button::State::new(),
                 reset_button_state: button::State::new(),
             })
@@ -299,161 +370,197 @@ impl SPModelInfo {
             })
             .collect();
 
-        let num_buffers = NUM_BUFFERS_SIDE * NUM_BUFFERS_SIDE;
         SPModelInfo {
             compiled_model,
-            state: vec![],
+            state: StateStore::default(),
             operations,
             intentions,
-            buffers_locations: (0..num_buffers).map(|_| BufferLocation::default()).collect(),
-            estimated_locations: (0..num_buffers).map(|_| BufferLocation::default()).collect(),
+            goal_layout: GoalLayout::default(),
+            buffers_locations: vec![],
+            estimated_locations: vec![],
             order_button: button::State::default(),
             update_button: button::State::default(),
+            apply_edits_button: button::State::default(),
+            expanded: HashSet::new(),
+            group_buttons: HashMap::new(),
+            selected_operation: None,
+            close_detail_button: button::State::default(),
+            expanded_transitions: HashSet::new(),
+            tplan_buttons: HashMap::new(),
+            browser: ModelBrowser::default(),
+        }
+    }
+
+    pub(crate) fn set_goal_layout(&mut self, layout: GoalLayout) {
+        self.buffers_locations = layout.cells.iter().map(|_| BufferLocation::default()).collect();
+        self.estimated_locations = layout.cells.iter().map(|_| BufferLocation::default()).collect();
+        self.goal_layout = layout;
+    }
+
+    /// The chosen value of every cell in the goal grid.
+    pub(crate) fn goal_values(&self, type_: BufferLocationType) -> Vec<(SPPath, SPValue)> {
+        let locations = match type_ {
+            BufferLocationType::Estimated => &self.estimated_locations,
+            BufferLocationType::Goal => &self.buffers_locations,
+        };
+        self.goal_layout.cells
+            .iter()
+            .zip(locations.iter())
+            .map(|(c, bl)| (c.path.clone(), c.values.get(bl.value).cloned().unwrap_or(SPValue::Unknown)))
+            .collect()
+    }
+
+    /// Take over the state, pending edits and what was open from the
+    /// info of the previous model.
+    pub(crate) fn keep_from(&mut self, old: SPModelInfo) {
+        self.state = old.state;
+        self.expanded = old.expanded;
+        self.group_buttons = old.group_buttons;
+        self.selected_operation = old.selected_operation
+            .filter(|p| self.operations.iter().any(|o| &o.op.path == p));
+        self.expanded_transitions = old.expanded_transitions;
+        self.tplan_buttons = old.tplan_buttons;
+        self.browser = old.browser;
+    }
+
+    pub(crate) fn variable(&self, path: &SPPath) -> Option<&Variable> {
+        model_variables(&self.compiled_model.model).find(|v| &v.path == path)
+    }
+
+    /// Parse pending edits into values using the declared type and domain
+    /// of each variable. Edits that parse are cleared, the others get an
+    /// error to show next to the row. Only looks at `only` if given.
+    pub(crate) fn take_state_edits(&mut self, only: Option<&SPPath>) -> Vec<(SPPath, SPValue)> {
+        let pending: Vec<SPPath> = match only {
+            Some(p) => vec![p.clone()],
+            None => self.state.iter().filter(|si| !si.new_value.is_empty()).map(|si| si.path.clone()).collect(),
+        };
+        let mut parsed = vec![];
+        for path in pending {
+            let si = match self.state.get(&path) {
+                Some(si) if !si.new_value.is_empty() => si,
+                _ => continue,
+            };
+            let result = match self.variable(&si.path) {
+                Some(v) => parse_value(&si.new_value, v.value_type, &v.domain),
+                // not part of the model (e.g. runner state), keep the current type.
+                None => parse_value(&si.new_value, si.value.has_type(), &[]),
+            };
+            let si = self.state.get_mut(&path).expect("checked above");
+            match result {
+                Ok(value) => {
+                    parsed.push((si.path.clone(), value));
+                    si.new_value.clear();
+                    si.edit_error = None;
+                },
+                Err(e) => si.edit_error = Some(e),
+            }
         }
+        parsed
     }
 
-    pub(crate) fn view_ops(&mut self) -> Element<Message> {
-        self.operations
+    pub(crate) fn view_ops(&mut self, filter: &str, read_only: bool) -> Element<Message> {
+        // details of the clicked operation, evaluated against the current
+        // state. Only built when an operation is selected.
+        let state = &self.state;
+        let detail = self.selected_operation.as_ref()
+            .and_then(|p| self.operations.iter().find(|o| &o.op.path == p))
+            .map(|o| view_operation_detail(&o.op, &state.to_state(), &mut self.close_detail_button));
+
+        let matcher = SkimMatcherV2::default().ignore_case();
+        let mut hidden = 0;
+        let ops = self.operations
             .iter_mut()
             .fold(Column::new().spacing(10), |col, o| {
-                let state_value = self.state.iter()
-                    .find(|s| s.path == o.op.path)
-                    .map(|s| s.value.to_string()).unwrap_or("[no state]".into());
-                col.push(o.view(&state_value))
-            })
-            .into()
+                match matcher.fuzzy_indices(&o.op.path.to_string(), filter) {
+                    Some((_, indices)) => {
+                        let state_value = self.state.value(&o.op.path)
+                            .map(|v| v.to_string()).unwrap_or("[no state]".into());
+                        col.push(o.view(&state_value, &indices, read_only))
+                    },
+                    None => {
+                        hidden += 1;
+                        col
+                    },
+                }
+            });
+        match detail {
+            Some(detail) => Column::new()
+                .spacing(20)
+                .push(detail)
+                .push(with_hidden_count(ops, hidden))
+                .into(),
+            None => with_hidden_count(ops, hidden),
+        }
     }
 
-    pub(crate) fn view_ints(&mut self) -> Element<Message> {
-        self.intentions
+    pub(crate) fn view_ints(&mut self, filter: &str, read_only: bool) -> Element<Message> {
+        let matcher = SkimMatcherV2::default().ignore_case();
+        let mut hidden = 0;
+        let ints = self.intentions
             .iter_mut()
             .fold(Column::new().spacing(10), |col, i| {
-                let state_value = self.state.iter()
-                    .find(|s| s.path == i.i.path)
-                    .map(|s| s.value.to_string()).unwrap_or("[no state]".into());
-                col.push(i.view(&state_value))
-            })
-            .into()
+                match matcher.fuzzy_indices(&i.i.path.to_string(), filter) {
+                    Some((_, indices)) => {
+                        let state_value = self.state.value(&i.i.path)
+                            .map(|v| v.to_string()).unwrap_or("[no state]".into());
+                        col.push(i.view(&state_value, &indices, read_only))
+                    },
+                    None => {
+                        hidden += 1;
+                        col
+                    },
+                }
+            });
+        with_hidden_count(ints, hidden)
     }
 
-    pub(crate) fn view_tplan(&mut self) -> Element<Message> {
-        // temp hack to get transitions, move this out later.
-        let model = &self.compiled_model.model;
-        let mut transitions: Vec<Transition> = model.resources
-            .iter()
-            .flat_map(|r| r.transitions.clone())
-            .collect();
-
-        let global_transitions = model.global_transitions.clone();
-        transitions.extend(global_transitions);
-        // end temp hack
+    pub(crate) fn view_tplan(&mut self, filter: &str) -> Element<Message> {
+        let transitions = model_transitions(&self.compiled_model.model);
 
         let goal_p = SPPath::from_slice(&["runner", "transition_goal"]);
-        let goal_str = self.state.iter()
-            .find(|si| si.path == goal_p)
-            .map(|si| si.value.to_string()).unwrap_or("no goal".to_string());
-
-        let plan_idx = self.state.iter().find_map(|si| {
-            if si.path == SPPath::from_slice(&["runner", "plans", "0"]) {
-                if let SPValue::Int32(idx) = &si.value {
-                    return Some(*idx);
-                }
-            }
-            None
-        }).unwrap_or(0);
+        let goal_str = self.state.value(&goal_p)
+            .map(|v| v.to_string()).unwrap_or("no goal".to_string());
 
-        let p_path = SPPath::from_slice(&["runner", "transition_plan"]);
-        let paths = self.state.iter().find_map(|si| {
-            if si.path == p_path {
-                if let SPValue::Array(SPValueType::Path, v) = &si.value {
-                    Some(v.iter().map(|e| e.to_string()).collect())
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        }).unwrap_or(vec![]);
-
-        let mut idx = 0;
-        let mut path_info = vec![];
-        for p in paths {
-            let trans = transitions.iter().find(|t| t.path().to_string() == p);
-            if let Some(trans) = &trans {
-                if trans.type_ == TransitionType::Controlled {
-                    idx+=1;
-                }
-            }
-            path_info.push((p, trans, idx));
-        }
+        let plan_idx = match self.state.value(&SPPath::from_slice(&["runner", "plans", "0"])) {
+            Some(SPValue::Int32(idx)) => *idx,
+            _ => 0,
+        };
 
-        let s = self.get_spstate();
+        let p_path = SPPath::from_slice(&["runner", "transition_plan"]);
+        let paths = plan_paths(self.state.value(&p_path));
 
-        let plan_cols: Element<Message> = path_info
-            .iter()
-            .fold(Column::new().spacing(10), |col, (path, trans, idx)| {
-                let mut guard = false;
-                let mut runner_guard = false;
-                let mut show_guards = false;
-                let color = match trans {
-                    Some(trans) if trans.type_ == TransitionType::Controlled => {
-                        if idx > &plan_idx {
-                            guard = trans.guard.eval(&s);
-                            runner_guard = trans.runner_guard.eval(&s);
-                            show_guards = true;
-                            [0.3, 0.3, 0.3] // later in plan
-                        } else if idx == &plan_idx {
-                            [0.0, 0.0, 0.5] // just started
-                        } else {
-                            [0.0, 0.5, 0.0] // already done
-                        }
-                    },
-                    Some(_) => [0.5, 0.5, 0.5], // effects are shaded
-                    None => [0.8, 0.8, 0.8], // trans does not exist
-                };
-
-                let guard_str = if show_guards {
-                    format!("g: {} / rg: {}", guard, runner_guard)
-                } else {
-                    String::new()
-                };
-                col.push(
-                    Row::new()
-                        .push(Column::new().width(Length::FillPortion(3))
-                              .push(Text::new(path).color(color)))
-                        .push(Column::new().width(Length::FillPortion(1))
-                              .push(Text::new(guard_str).color(color))))
-            })
-            .into();
+        // an empty plan has no guards to evaluate, skip building the state.
+        let s = (!paths.is_empty()).then(|| self.get_spstate());
+        let states: Vec<&SPState> = s.iter().collect();
+        view_transition_plan(goal_str, &paths, plan_idx, &transitions, &states, filter,
+                             &mut self.tplan_buttons, &self.expanded_transitions,
+                             Message::ToggleTransitionDetail)
+    }
 
-        Column::new()
-            .spacing(5)
-            .push(Text::new(goal_str).size(30))
-            .push(Column::new().push(plan_cols)).into()
+    pub(crate) fn view_model(&mut self, filter: &str) -> Element<Message> {
+        self.browser.view(&self.compiled_model.model, &self.state, filter)
     }
 
-    pub(crate) fn view_oplan(&mut self) -> Element<Message> {
+    pub(crate) fn view_oplan(&mut self, filter: &str) -> Element<Message> {
         let goal_p = SPPath::from_slice(&["runner", "operation_goal"]);
-        let goal_str = self.state.iter()
-            .find(|si| si.path == goal_p)
-            .map(|si| si.value.to_string()).unwrap_or("no goal".to_string());
+        let goal_str = self.state.value(&goal_p)
+            .map(|v| v.to_string()).unwrap_or("no goal".to_string());
 
         let p_path = SPPath::from_slice(&["runner", "operation_plan"]);
-        let paths = self.state.iter().find_map(|si| {
-            if si.path == p_path {
-                if let SPValue::Array(SPValueType::Path, v) = &si.value {
-                    Some(v.iter().map(|e| e.to_string()).collect())
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        }).unwrap_or(vec![]);
+        let paths: Vec<String> = plan_paths(self.state.value(&p_path));
+        let matcher = SkimMatcherV2::default().ignore_case();
+        let mut hidden = 0;
         let plan_cols: Element<Message> = paths
             .iter()
-            .fold(Column::new().spacing(10), |col, path| {
-                col.push(Text::new(path))
+            .fold(Column::new().spacing(10), |col, path: &String| {
+                match matcher.fuzzy_indices(path, filter) {
+                    Some((_, indices)) => col.push(highlighted(path, &indices, 20, [0.0, 0.0, 0.0])),
+                    None => {
+                        hidden += 1;
+                        col
+                    },
+                }
             })
             .into();
 
@@ -461,112 +568,461 @@ impl SPModelInfo {
             .height(Length::Fill)
             .spacing(20)
             .push(Text::new(goal_str).size(30))
-            .push(Column::new().push(plan_cols)).into()
+            .push(with_hidden_count(plan_cols, hidden)).into()
     }
 
-    pub(crate) fn view_demo_goal(&mut self) -> Element<Message> {
+    pub(crate) fn view_demo_goal(&mut self, read_only: bool) -> Element<Message> {
+        if self.goal_layout.cells.is_empty() {
+            return Text::new("The goal layout has no cells").size(20).into();
+        }
+        let layout = &self.goal_layout;
+
         let col = Column::new().spacing(10);
         let col = col.push(Text::new("Set system state").size(20));
+        let col = col.push(view_goal_grid(layout, &mut self.estimated_locations, BufferLocationType::Estimated));
+        let col = if read_only {
+            col
+        } else {
+            col.push(Button::new(&mut self.update_button, Text::new("Update state"))
+                     .padding(10)
+                     .style(crate::style::Button::Primary)
+                     .on_press(Message::SetEstimatedCylinders))
+        };
 
-        let grid: Element<Message> =
-            self.estimated_locations
-            .chunks_mut(NUM_BUFFERS_SIDE)
-            .enumerate()
-            .fold(Column::new().spacing(10), |row, (y, bl) | {
-                let r = bl.iter_mut().enumerate()
-                    .fold(Row::new().spacing(10), |col, (x, b)| {
-                        let val_str = if b.value { "cylinder" } else { "empty" };
-                        let text = format!("{},{}: {}", x, y, val_str);
-                        let message = Message::BufferButton(
-                            BufferLocationType::Estimated,
-                            y*NUM_BUFFERS_SIDE+x, !b.value);
-                        let button = Button::new(&mut b.button, Text::new(text))
-                            .on_press(message);
-                        col.push(button)
-                    });
-                row.push(r)
-            })
-            .into();
+        ///////////////
 
-        let col = col.push(grid);
+        let col = col.push(Text::new("Create an order").size(20));
+        let col = col.push(view_goal_grid(layout, &mut self.buffers_locations, BufferLocationType::Goal));
+        let col = if read_only {
+            col
+        } else {
+            col.push(Button::new(&mut self.order_button, Text::new("Make order"))
+                     .padding(10)
+                     .style(crate::style::Button::Primary)
+                     .on_press(Message::SendGoalCylinders))
+        };
 
-        let button = Button::new(&mut self.update_button, Text::new("Update state"))
-            .padding(10)
-            .style(crate::style::Button::Primary)
-            .on_press(Message::SetEstimatedCylinders);
-        let col = col.push(button);
+        col.into()
+    }
 
+    pub(crate) fn view_state<'a>(&'a mut self,
+                                 filter: &'a str,
+                                 scroll_state: &'a mut scrollable::State,
+                                 read_only: bool) -> Element<Message> {
+        let matcher = SkimMatcherV2::default().ignore_case();
 
-        ///////////////
+        let pending = self.state.iter().filter(|si| !si.new_value.is_empty()).count();
+        let apply_button = Button::new(&mut self.apply_edits_button,
+                                       Text::new(format!("Apply all pending edits ({})", pending)))
+            .padding(8)
+            .style(crate::style::Button::Primary);
+        let apply_button = if pending > 0 {
+            apply_button.on_press(Message::ApplyStateEdits)
+        } else {
+            apply_button
+        };
 
-        let col = col.push(Text::new("Create an order").size(20));
+        let variables: HashMap<&SPPath, &Variable> = model_variables(&self.compiled_model.model)
+            .map(|v| (&v.path, v))
+            .collect();
+        let highlights: Vec<Option<Vec<usize>>> = self.state.iter()
+            .map(|si| matcher.fuzzy_indices(&si.path.to_string(), filter).map(|(_, i)| i))
+            .collect();
+        let matches: Vec<bool> = highlights.iter().map(|h| h.is_some()).collect();
+        let hidden = matches.iter().filter(|m| !**m).count();
+        let changed: Vec<bool> = self.state.iter().map(|si| self.state.changed().contains(&si.path)).collect();
+        let paths: Vec<&SPPath> = self.state.iter().map(|si| &si.path).collect();
+        let rows = tree_rows(&paths, &matches, &self.expanded);
 
-        let grid: Element<Message> =
-            self.buffers_locations
-            .chunks_mut(NUM_BUFFERS_SIDE)
-            .enumerate()
-            .fold(Column::new().spacing(10), |row, (y, bl) | {
-                let r = bl.iter_mut().enumerate()
-                    .fold(Row::new().spacing(10), |col, (x, b)| {
-                        let val_str = if b.value { "cylinder" } else { "empty" };
-                        let text = format!("{},{}: {}", x, y, val_str);
-                        let message = Message::BufferButton(
-                            BufferLocationType::Goal,
-                            y*NUM_BUFFERS_SIDE+x, !b.value);
-                        col.push(Button::new(&mut b.button, Text::new(text))
-                                 .on_press(message))
-                    });
-                row.push(r)
-            })
-            .into();
+        for row in &rows {
+            if let TreeRow::Group { path, .. } = row {
+                self.group_buttons.entry(path.clone()).or_default();
+            }
+        }
+        let mut buttons: HashMap<&SPPath, &mut button::State> = self.group_buttons.iter_mut().collect();
+        let mut infos: Vec<Option<&mut StateInfo>> = self.state.iter_mut().map(Some).collect();
+        let filtering = !filter.is_empty();
+        let expanded = &self.expanded;
 
-        let col = col.push(grid);
+        let state: Element<Message> = rows.into_iter()
+            .fold(Column::new().spacing(5), |col, row| match row {
+                TreeRow::Group { path, depth, matching } => {
+                    let is_expanded = expanded.contains(&path);
+                    let button_state = buttons.remove(&path).expect("button created above");
+                    col.push(view_state_group(button_state, path, depth, matching,
+                                              is_expanded, filtering))
+                },
+                TreeRow::Leaf { index, depth } => match infos[index].take() {
+                    Some(si) => {
+                        let editor = if read_only {
+                            ValueEditor::ReadOnly
+                        } else {
+                            ValueEditor::for_variable(variables.get(&si.path).copied(), &si.value)
+                        };
+                        let highlight = highlights[index].as_deref().unwrap_or(&[]);
+                        col.push(view_state_row(si, editor, depth, highlight, changed[index]))
+                    },
+                    None => col,
+                },
+            }).into();
 
-        let button = Button::new(&mut self.order_button, Text::new("Make order"))
-            .padding(10)
-            .style(crate::style::Button::Primary)
-            .on_press(Message::SendGoalCylinders);
-        let col = col.push(button);
+        let col = Column::new().spacing(10);
+        let col = if read_only { col } else { col.push(apply_button) };
+        col
+            .push(Scrollable::new(scroll_state)
+                  .push(Container::new(with_hidden_count(state, hidden))))
+            .into()
+    }
+}
 
-        col.into()
+
+/// A transition plan, controlled transitions up to `plan_idx` are done.
+/// Row `i` has its guards evaluated against `states[i]`, or the last state
+/// if there are fewer states than rows. `states` may only be empty when
+/// `paths` is. Rows can be expanded to show them.
+pub(crate) fn view_transition_plan<'a>(title: String, paths: &[String], plan_idx: i32,
+                                       transitions: &[Transition], states: &[&SPState], filter: &str,
+                                       buttons: &'a mut HashMap<String, button::State>,
+                                       expanded: &HashSet<String>,
+                                       on_toggle: fn(String) -> Message) -> Element<'a, Message> {
+    let mut idx = 0;
+    let mut path_info = vec![];
+    for (i, p) in paths.iter().cloned().enumerate() {
+        let trans = transitions.iter().find(|t| t.path().to_string() == p);
+        if let Some(trans) = &trans {
+            if trans.type_ == TransitionType::Controlled {
+                idx+=1;
+            }
+        }
+        let s = states[i.min(states.len() - 1)];
+        path_info.push((p, trans, idx, s));
     }
 
-    pub(crate) fn view_state<'a>(&'a mut self,
-                                 filter: &'a str,
-                                 scroll_state: &'a mut scrollable::State) -> Element<Message> {
-        let matcher = SkimMatcherV2::default().ignore_case();
-        self.state.sort_by(|a,b| a.path.cmp(&b.path));
-        let state: Element<Message> = self.state.iter_mut()
-            .filter(|si| matcher.fuzzy_match(&si.path.to_string(), filter).is_some())
-            .fold(Column::new().spacing(5),
-                  |col, si| col.push(
-                      view_state_row(si.path.clone(),
-                                     si.value.to_string(),
-                                     &si.new_value,
-                                     &mut si.new_value_state))).into();
+    for (p, trans, _, _) in &path_info {
+        if trans.is_some() {
+            buttons.entry(p.clone()).or_default();
+        }
+    }
+    let mut buttons: HashMap<&String, &mut button::State> = buttons.iter_mut().collect();
+
+    let matcher = SkimMatcherV2::default().ignore_case();
+    let mut hidden = 0;
+    let plan_cols: Element<Message> = path_info
+        .iter()
+        .filter_map(|(path, trans, idx, s)| match matcher.fuzzy_indices(path, filter) {
+            Some((_, indices)) => Some((path, trans, idx, s, indices)),
+            None => {
+                hidden += 1;
+                None
+            },
+        })
+        .fold(Column::new().spacing(10), |col, (path, trans, idx, s, indices)| {
+            let mut guard = false;
+            let mut runner_guard = false;
+            let mut show_guards = false;
+            let color = match trans {
+                Some(trans) if trans.type_ == TransitionType::Controlled => {
+                    if idx > &plan_idx {
+                        guard = trans.guard.eval(s);
+                        runner_guard = trans.runner_guard.eval(s);
+                        show_guards = true;
+                        [0.3, 0.3, 0.3] // later in plan
+                    } else if idx == &plan_idx {
+                        [0.0, 0.0, 0.5] // just started
+                    } else {
+                        [0.0, 0.5, 0.0] // already done
+                    }
+                },
+                Some(_) => [0.5, 0.5, 0.5], // effects are shaded
+                None => [0.8, 0.8, 0.8], // trans does not exist
+            };
+
+            let guard_str = if show_guards {
+                format!("g: {} / rg: {}", guard, runner_guard)
+            } else {
+                String::new()
+            };
+            let is_expanded = expanded.contains(path.as_str());
+            let row = Row::new().spacing(10).align_items(Alignment::Center);
+            let row = match (trans, buttons.remove(path)) {
+                (Some(_), Some(b)) => {
+                    let marker = if is_expanded { "-" } else { "+" };
+                    row.push(Button::new(b, Text::new(marker).size(16))
+                             .padding(4)
+                             .on_press(on_toggle(path.clone())))
+                },
+                _ => row,
+            };
+            let col = col.push(
+                row
+                    .push(Column::new().width(Length::FillPortion(3))
+                          .push(highlighted(path, &indices, 20, color)))
+                    .push(Column::new().width(Length::FillPortion(1))
+                          .push(Text::new(guard_str).color(color))));
+            match trans {
+                Some(trans) if is_expanded => col.push(view_transition_detail(trans, s)),
+                _ => col,
+            }
+        })
+        .into();
+
+    Column::new()
+        .spacing(5)
+        .push(Text::new(title).size(30))
+        .push(with_hidden_count(plan_cols, hidden)).into()
+}
+
+/// One grid of the demo goal view, clicking a cell moves it to its next value.
+fn view_goal_grid<'a>(layout: &GoalLayout, locations: &'a mut [BufferLocation],
+                      type_: BufferLocationType) -> Element<'a, Message> {
+    locations
+        .chunks_mut(layout.columns)
+        .zip(layout.cells.chunks(layout.columns))
+        .enumerate()
+        .fold(Column::new().spacing(10), |rows, (y, (bls, cells))| {
+            let r = bls.iter_mut().zip(cells.iter()).enumerate()
+                .fold(Row::new().spacing(10), |row, (x, (b, cell))| {
+                    let value = cell.values.get(b.value).map(|v| v.to_string()).unwrap_or_default();
+                    let text = format!("{}: {}", cell.label, value);
+                    let next = (b.value + 1) % cell.values.len().max(1);
+                    let message = Message::BufferButton(type_, y * layout.columns + x, next);
+                    row.push(Button::new(&mut b.button, Text::new(text))
+                             .on_press(message))
+                });
+            rows.push(r)
+        })
+        .into()
+}
 
-        Scrollable::new(scroll_state)
-                    .push(Container::new(state)).into()
+pub(crate) fn parse_value(text: &str, value_type: SPValueType, domain: &[SPValue]) -> Result<SPValue, String> {
+    let text = text.trim();
+    let value = match value_type {
+        SPValueType::Bool => match text {
+            "true" => SPValue::Bool(true),
+            "false" => SPValue::Bool(false),
+            _ => return Err(format!("'{}' is not a bool", text)),
+        },
+        SPValueType::Int32 => text.parse::<i32>().map(SPValue::Int32)
+            .map_err(|_| format!("'{}' is not an integer", text))?,
+        SPValueType::Float32 => text.parse::<f32>().map(SPValue::Float32)
+            .map_err(|_| format!("'{}' is not a number", text))?,
+        SPValueType::String => SPValue::String(text.to_string()),
+        t => return Err(format!("cannot edit values of type {:?}", t)),
+    };
+
+    if !domain.is_empty() && !domain.contains(&value) {
+        let domain: Vec<_> = domain.iter().map(|v| v.to_string()).collect();
+        return Err(format!("{} not in domain [{}]", value, domain.join(", ")));
     }
+    Ok(value)
 }
 
+/// Text where the characters matched by the filter are highlighted.
+pub(crate) fn highlighted<'a>(text: &str, highlight: &[usize],
+                              size: u16, color: impl Into<Color> + Copy) -> Row<'a, Message> {
+    let segment_text = |segment: &str, matched: bool| {
+        let text = Text::new(segment).size(size);
+        if matched {
+            text.color([0.9, 0.45, 0.0])
+        } else {
+            text.color(color)
+        }
+    };
+
+    let mut row = Row::new();
+    let mut segment = String::new();
+    let mut segment_matched = false;
+    for (i, c) in text.chars().enumerate() {
+        let matched = highlight.contains(&i);
+        if matched != segment_matched && !segment.is_empty() {
+            row = row.push(segment_text(&segment, segment_matched));
+            segment.clear();
+        }
+        segment_matched = matched;
+        segment.push(c);
+    }
+    if !segment.is_empty() {
+        row = row.push(segment_text(&segment, segment_matched));
+    }
+    row
+}
+
+pub(crate) fn with_hidden_count<'a>(content: impl Into<Element<'a, Message>>,
+                                    hidden: usize) -> Element<'a, Message> {
+    let col = Column::new().spacing(10);
+    let col = if hidden > 0 {
+        col.push(Text::new(format!("{} hidden by filter", hidden)).size(14).color([0.5, 0.5, 0.5]))
+    } else {
+        col
+    };
+    col.push(content).into()
+}
+
+/// The paths in a plan published by the runner.
+fn plan_paths(value: Option<&SPValue>) -> Vec<String> {
+    match value {
+        Some(SPValue::Array(SPValueType::Path, v)) => v.iter().map(|e| e.to_string()).collect(),
+        _ => vec![],
+    }
+}
+
+pub(crate) fn model_variables(model: &Model) -> impl Iterator<Item = &Variable> {
+    model.resources.iter().flat_map(|r| r.variables.iter())
+}
+
+/// Resource and global transitions.
+pub(crate) fn model_transitions(model: &Model) -> Vec<Transition> {
+    model.resources
+        .iter()
+        .flat_map(|r| r.transitions.iter())
+        .chain(model.global_transitions.iter())
+        .cloned()
+        .collect()
+}
+
+pub(crate) fn view_state_group(button_state: &mut button::State, path: SPPath, depth: usize,
+                               matching: usize, expanded: bool, filtering: bool) -> Element<Message> {
+    let marker = if expanded { "-" } else { "+" };
+    let label = if filtering {
+        format!("{} {} ({} matching)", marker, path.leaf(), matching)
+    } else {
+        format!("{} {}", marker, path.leaf())
+    };
 
-pub(crate) fn view_state_row<'a>(path: SPPath, value: String,
-                                 new_value: &str, text_state: &'a mut text_input::State) -> Element<'a, Message> {
+    Row::new()
+        .align_items(Alignment::Center)
+        .push(Space::with_width(Length::Units((depth * 20) as u16)))
+        .push(Button::new(button_state, Text::new(label).size(16))
+              .padding(4)
+              .on_press(Message::ToggleStateGroup(path)))
+        .into()
+}
+
+pub(crate) fn view_state_row<'a>(si: &'a mut StateInfo, editor: ValueEditor, depth: usize,
+                                 highlight: &[usize], changed: bool) -> Element<'a, Message> {
+    let path = si.path.clone();
+    let mut error = si.edit_error.clone();
+    let input: Element<Message> = match editor {
+        ValueEditor::Toggle => {
+            let active = match si.new_value.as_str() {
+                "true" => true,
+                "false" => false,
+                _ => si.value == SPValue::Bool(true),
+            };
+            Toggler::new(active, None,
+                         move |b| Message::StateValueSet(path.clone(), b.to_string()))
+                .into()
+        },
+        ValueEditor::Pick(domain) => {
+            let selected = if si.new_value.is_empty() {
+                Some(si.value.to_string())
+            } else {
+                Some(si.new_value.clone())
+            };
+            PickList::new(&mut si.pick_state, domain, selected,
+                          move |v| Message::StateValueSet(path.clone(), v))
+                .into()
+        },
+        ValueEditor::Number(value_type) => {
+            // validate while typing so bad numbers never reach the runner.
+            if error.is_none() && !si.new_value.is_empty() {
+                error = parse_value(&si.new_value, value_type, &[]).err();
+            }
+            let placeholder = match value_type {
+                SPValueType::Int32 => "integer",
+                _ => "number",
+            };
+            let submit_path = path.clone();
+            TextInput::new(
+                &mut si.new_value_state,
+                placeholder,
+                &si.new_value,
+                move |new_value| Message::StateValueEdit(path.clone(), new_value)
+            )
+            .on_submit(Message::StateValueCommit(submit_path))
+            .into()
+        },
+        ValueEditor::ReadOnly => Space::with_width(Length::Shrink).into(),
+        ValueEditor::Text => {
+            let submit_path = path.clone();
+            TextInput::new(
+                &mut si.new_value_state,
+                "",
+                &si.new_value,
+                move |new_value| Message::StateValueEdit(path.clone(), new_value)
+            )
+            .on_submit(Message::StateValueCommit(submit_path))
+            .into()
+        },
+    };
+
+    // the filter matches on the full path but we only show the leaf.
+    let offset = si.path.to_string().chars().count() - si.path.leaf().chars().count();
+    let leaf_highlight: Vec<usize> = highlight.iter()
+        .filter(|i| **i >= offset)
+        .map(|i| i - offset)
+        .collect();
+
+    let input = Column::new()
+        .width(Length::FillPortion(2))
+        .push(input);
+    let input = match error {
+        Some(e) => input.push(Text::new(e).size(12).color([0.8, 0.1, 0.1])),
+        None => input,
+    };
+
+    // values touched by the last state update stand out.
+    let value_color = if changed { [0.0, 0.45, 0.0] } else { [0.2, 0.2, 0.2] };
     Row::new()
         .spacing(20)
+        .push(Space::with_width(Length::Units((depth * 20) as u16)))
         .push(Column::new()
               .width(Length::FillPortion(3))
-              .push(Text::new(path.to_string()).size(16).color([0.3, 0.3, 0.3])))
+              .push(highlighted(&si.path.leaf(), &leaf_highlight, 16, [0.3, 0.3, 0.3])))
         .push(Column::new()
               .width(Length::FillPortion(1))
-              .push(Text::new(&value).size(16).height(Length::Units(30)).color([0.2, 0.2, 0.2])))
-        .push(
-            TextInput::new(
-                text_state,
-                "",
-                new_value,
-                move |new_value| Message::StateValueEdit(path.clone(), new_value)
-            ))
+              .push(Text::new(si.value.to_string()).size(16).height(Length::Units(30)).color(value_color)))
+        .push(input)
         .into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_models;
+
+    #[test]
+    fn parse_value_by_type() {
+        assert_eq!(parse_value(" true ", SPValueType::Bool, &[]), Ok(SPValue::Bool(true)));
+        assert_eq!(parse_value("-3", SPValueType::Int32, &[]), Ok(SPValue::Int32(-3)));
+        assert_eq!(parse_value("0.5", SPValueType::Float32, &[]), Ok(SPValue::Float32(0.5)));
+        assert_eq!(parse_value("home", SPValueType::String, &[]), Ok(SPValue::String("home".into())));
+    }
+
+    #[test]
+    fn parse_value_rejects_bad_text() {
+        assert!(parse_value("yes", SPValueType::Bool, &[]).is_err());
+        assert!(parse_value("1.5", SPValueType::Int32, &[]).is_err());
+        assert!(parse_value("fast", SPValueType::Float32, &[]).is_err());
+        assert!(parse_value("a/b", SPValueType::Path, &[]).is_err());
+    }
+
+    #[test]
+    fn editors_only_for_types_we_can_parse() {
+        let pos = test_models::variable("r/pos", SPValueType::Int32, vec![SPValue::Int32(0)]);
+        assert!(matches!(ValueEditor::for_variable(Some(&pos), &SPValue::Int32(0)), ValueEditor::Pick(_)));
+        let target = test_models::variable("r/target", SPValueType::Path,
+                                           vec![SPValue::Path(test_models::path("r/home"))]);
+        let current = SPValue::Path(test_models::path("r/home"));
+        assert!(matches!(ValueEditor::for_variable(Some(&target), &current), ValueEditor::ReadOnly));
+        assert!(matches!(ValueEditor::for_variable(None, &SPValue::Float32(1.0)), ValueEditor::Number(_)));
+    }
+
+    #[test]
+    fn parse_value_checks_the_domain() {
+        let domain = vec![SPValue::Int32(0), SPValue::Int32(1)];
+        assert_eq!(parse_value("1", SPValueType::Int32, &domain), Ok(SPValue::Int32(1)));
+        let e = parse_value("2", SPValueType::Int32, &domain).unwrap_err();
+        assert_eq!(e, "2 not in domain [0, 1]");
+    }
+}
@@ -0,0 +1,220 @@
+use clap::Parser;
+use serde::Deserialize;
+use crate::state_channel::ChannelMode;
+
+/// Command line arguments. Anything given here overrides the config file.
+#[derive(Debug, Parser)]
+#[clap(name = "sp-op-viewer", about = "Operation viewer for the sp runner")]
+struct Args {
+    /// TOML file with settings
+    #[clap(long)]
+    config: Option<String>,
+    /// ros namespace of the sp runner
+    #[clap(long)]
+    namespace: Option<String>,
+    #[clap(long)]
+    node_name: Option<String>,
+    #[clap(long)]
+    get_model_service: Option<String>,
+    #[clap(long)]
+    set_model_service: Option<String>,
+    #[clap(long)]
+    set_state_service: Option<String>,
+    #[clap(long)]
+    state_topic: Option<String>,
+    #[clap(long)]
+    get_model_timeout_ms: Option<u64>,
+    #[clap(long)]
+    set_model_timeout_ms: Option<u64>,
+    #[clap(long)]
+    set_state_timeout_ms: Option<u64>,
+    /// warn when no state has arrived for this long
+    #[clap(long)]
+    stale_after_ms: Option<u64>,
+    #[clap(long)]
+    window_width: Option<u32>,
+    #[clap(long)]
+    window_height: Option<u32>,
+    /// run without ros against a compiled model loaded from this json file
+    #[clap(long)]
+    mock_model: Option<String>,
+    /// directory for the audit log of commands sent to the runner
+    #[clap(long)]
+    audit_dir: Option<String>,
+    /// only watch, never send set_state or set_model
+    #[clap(long)]
+    read_only: bool,
+    /// TOML file describing the demo goal grid
+    #[clap(long)]
+    goal_layout: Option<String>,
+    /// without a layout file, put every variable matching this in the grid
+    #[clap(long)]
+    goal_pattern: Option<String>,
+    #[clap(long)]
+    goal_columns: Option<usize>,
+    /// how many steps the local planner searches for a plan
+    #[clap(long)]
+    plan_max_steps: Option<u32>,
+    /// keep only the newest state or queue every state message
+    #[clap(long, arg_enum)]
+    state_channel: Option<ChannelMode>,
+    /// how many state messages the lossless queue holds before merging
+    #[clap(long)]
+    state_queue: Option<usize>,
+}
+
+/// Viewer settings. All fields are optional in the config file, e.g.
+///
+/// ```toml
+/// namespace = "cell_2"
+/// set_state_timeout_ms = 1000
+/// ```
+///
+/// Unknown keys are an error, so a typo does not silently fall back to
+/// the default.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub namespace: String,
+    pub node_name: String,
+    pub get_model_service: String,
+    pub set_model_service: String,
+    pub set_state_service: String,
+    pub state_topic: String,
+    pub get_model_timeout_ms: u64,
+    pub set_model_timeout_ms: u64,
+    pub set_state_timeout_ms: u64,
+    pub stale_after_ms: u64,
+    pub window_width: u32,
+    pub window_height: u32,
+    pub mock_model: Option<String>,
+    pub audit_dir: String,
+    pub read_only: bool,
+    pub goal_layout: Option<String>,
+    pub goal_pattern: String,
+    pub goal_columns: Option<usize>,
+    pub plan_max_steps: u32,
+    pub state_channel: ChannelMode,
+    pub state_queue: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            namespace: String::new(),
+            node_name: "sp_op_viewer".into(),
+            get_model_service: "/sp/get_model".into(),
+            set_model_service: "/sp/set_model".into(),
+            set_state_service: "/sp/set_state".into(),
+            state_topic: "/sp/state_flat".into(),
+            get_model_timeout_ms: 1000,
+            set_model_timeout_ms: 1000,
+            set_state_timeout_ms: 500,
+            stale_after_ms: 3000,
+            window_width: 750,
+            window_height: 720,
+            mock_model: None,
+            audit_dir: ".".into(),
+            read_only: false,
+            goal_layout: None,
+            goal_pattern: "lab_scenario_1/product_state/buffer_*".into(),
+            goal_columns: None,
+            plan_max_steps: 30,
+            state_channel: ChannelMode::Latest,
+            state_queue: 1000,
+        }
+    }
+}
+
+impl Config {
+    /// Defaults, overridden by the config file, overridden by the command line.
+    pub fn load() -> Result<Config, String> {
+        Config::from_args(Args::parse())
+    }
+
+    fn from_args(args: Args) -> Result<Config, String> {
+        let mut config = match &args.config {
+            Some(path) => {
+                let toml = std::fs::read_to_string(path)
+                    .map_err(|e| format!("could not read {}: {}", path, e))?;
+                toml::from_str(&toml).map_err(|e| format!("could not parse {}: {}", path, e))?
+            },
+            None => Config::default(),
+        };
+
+        if let Some(v) = args.namespace { config.namespace = v; }
+        if let Some(v) = args.node_name { config.node_name = v; }
+        if let Some(v) = args.get_model_service { config.get_model_service = v; }
+        if let Some(v) = args.set_model_service { config.set_model_service = v; }
+        if let Some(v) = args.set_state_service { config.set_state_service = v; }
+        if let Some(v) = args.state_topic { config.state_topic = v; }
+        if let Some(v) = args.get_model_timeout_ms { config.get_model_timeout_ms = v; }
+        if let Some(v) = args.set_model_timeout_ms { config.set_model_timeout_ms = v; }
+        if let Some(v) = args.set_state_timeout_ms { config.set_state_timeout_ms = v; }
+        if let Some(v) = args.stale_after_ms { config.stale_after_ms = v; }
+        if let Some(v) = args.window_width { config.window_width = v; }
+        if let Some(v) = args.window_height { config.window_height = v; }
+        if args.mock_model.is_some() { config.mock_model = args.mock_model; }
+        if let Some(v) = args.audit_dir { config.audit_dir = v; }
+        if args.read_only { config.read_only = true; }
+        if args.goal_layout.is_some() { config.goal_layout = args.goal_layout; }
+        if let Some(v) = args.goal_pattern { config.goal_pattern = v; }
+        if args.goal_columns.is_some() { config.goal_columns = args.goal_columns; }
+        if let Some(v) = args.plan_max_steps { config.plan_max_steps = v; }
+        if let Some(v) = args.state_channel { config.state_channel = v; }
+        if let Some(v) = args.state_queue { config.state_queue = v; }
+
+        Ok(config)
+    }
+
+    /// Put a service or topic name under our namespace. Names are
+    /// given as if the runner lives in the root namespace.
+    pub fn resolve(&self, name: &str) -> String {
+        let namespace = self.namespace.trim_matches('/');
+        if namespace.is_empty() {
+            name.to_string()
+        } else {
+            format!("/{}/{}", namespace, name.trim_start_matches('/'))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Load with a config file holding `toml` and the given arguments.
+    fn load(name: &str, toml: &str, args: &[&str]) -> Result<Config, String> {
+        let path = std::env::temp_dir().join(format!("sp_op_viewer_{}_{}.toml", name, std::process::id()));
+        std::fs::write(&path, toml).unwrap();
+        let path = path.to_string_lossy().to_string();
+        let mut argv = vec!["sp-op-viewer", "--config", &path];
+        argv.extend(args);
+        let config = Config::from_args(Args::try_parse_from(argv).unwrap());
+        std::fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn cli_beats_file_beats_default() {
+        let toml = "namespace = \"from_file\"\nset_state_timeout_ms = 1000\n";
+        let config = load("precedence", toml, &["--set-state-timeout-ms", "200"]).unwrap();
+        assert_eq!(config.set_state_timeout_ms, 200);
+        assert_eq!(config.namespace, "from_file");
+        assert_eq!(config.get_model_timeout_ms, Config::default().get_model_timeout_ms);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        let e = load("typo", "plan_max_step = 10\n", &[]).unwrap_err();
+        assert!(e.contains("plan_max_step"), "{}", e);
+    }
+
+    #[test]
+    fn resolve_puts_names_under_the_namespace() {
+        let mut config = Config::default();
+        assert_eq!(config.resolve("/sp/state_flat"), "/sp/state_flat");
+        config.namespace = "/cell_2/".to_string();
+        assert_eq!(config.resolve("/sp/state_flat"), "/cell_2/sp/state_flat");
+    }
+}
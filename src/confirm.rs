@@ -0,0 +1,79 @@
+use iced::{button, Alignment, Button, Column, Element, Row, Text};
+use sp_domain::*;
+use crate::{Message, PendingIntention};
+
+/// What confirming does.
+#[derive(Debug, Clone)]
+pub enum Pending {
+    StateChange {
+        path: SPPath,
+        current: Option<SPValue>,
+        new_value: SPValue,
+    },
+    /// An intention the planner could not vouch for, `reason` says why.
+    Intention {
+        pending: PendingIntention,
+        reason: String,
+    },
+}
+
+/// Something waiting for the user to confirm it. While one is
+/// pending it is the only thing we show.
+#[derive(Debug, Clone)]
+pub struct Confirmation {
+    pub pending: Pending,
+    confirm_button: button::State,
+    cancel_button: button::State,
+}
+
+impl Confirmation {
+    pub fn new(pending: Pending) -> Self {
+        Confirmation {
+            pending,
+            confirm_button: button::State::new(),
+            cancel_button: button::State::new(),
+        }
+    }
+
+    pub fn view(&mut self) -> Element<Message> {
+        let (question, confirm, body) = match &self.pending {
+            Pending::StateChange { path, current, new_value } => {
+                let current = current.as_ref().map(|v| v.to_string()).unwrap_or("[no state]".into());
+                let body = Column::new()
+                    .spacing(20)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(path.to_string()).size(20))
+                    .push(Row::new()
+                          .spacing(20)
+                          .push(Text::new(current).size(24).color([0.5, 0.5, 0.5]))
+                          .push(Text::new("->").size(24))
+                          .push(Text::new(new_value.to_string()).size(24).color([0.8, 0.1, 0.1])));
+                ("Change the state of the runner?", "Confirm", body)
+            },
+            Pending::Intention { pending, reason } => {
+                let body = Column::new()
+                    .spacing(20)
+                    .align_items(Alignment::Center)
+                    .push(Text::new(reason.clone()).size(20).color([0.8, 0.1, 0.1]))
+                    .push(Text::new(format!("goal: {}", pending.post)).size(16));
+                ("Send the intention anyway?", "Send anyway", body)
+            },
+        };
+        Column::new()
+            .spacing(20)
+            .padding(20)
+            .align_items(Alignment::Center)
+            .push(Text::new(question).size(30))
+            .push(body)
+            .push(Row::new()
+                  .spacing(20)
+                  .push(Button::new(&mut self.cancel_button, Text::new("Cancel"))
+                        .padding(10)
+                        .on_press(Message::CancelConfirmation))
+                  .push(Button::new(&mut self.confirm_button, Text::new(confirm))
+                        .padding(10)
+                        .style(crate::style::Button::Primary)
+                        .on_press(Message::Confirm)))
+            .into()
+    }
+}
@@ -0,0 +1,116 @@
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// A service did not answer in time.
+    ServiceTimeout {
+        service: String,
+        timeout: Duration,
+    },
+    /// The call could not be made or the service returned an error.
+    ServiceError {
+        service: String,
+        message: String,
+    },
+    /// The runner answered but refused the request.
+    Rejected {
+        service: String,
+        message: String,
+        /// the full answer, kept for the audit log
+        response: String,
+    },
+    /// Json that does not match what we expected. `path` is where in the
+    /// document deserialization failed, e.g. `compiled_model.model.resources[2]`.
+    Parse {
+        what: String,
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    Io {
+        what: String,
+        message: String,
+    },
+    /// The local planner could not be run.
+    Planner {
+        message: String,
+    },
+    /// A cell of the goal layout file does not fit the model. `cell`
+    /// counts from 1 in the order of the file.
+    GoalLayout {
+        cell: usize,
+        path: String,
+        message: String,
+    },
+}
+
+impl Error {
+    /// Deserialize json, keeping track of where in the document it failed.
+    pub fn parse_json<T: DeserializeOwned>(what: &str, json: &str) -> Result<T, Error> {
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+            let path = e.path().to_string();
+            let inner = e.into_inner();
+            Error::Parse {
+                what: what.to_string(),
+                path,
+                line: inner.line(),
+                column: inner.column(),
+                message: inner.to_string(),
+            }
+        })
+    }
+
+    pub fn io(what: &str, error: std::io::Error) -> Error {
+        Error::Io {
+            what: what.to_string(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ServiceTimeout { service, timeout } => {
+                write!(f, "{} did not answer within {} ms", service, timeout.as_millis())
+            },
+            Error::ServiceError { service, message } => {
+                write!(f, "calling {} failed: {}", service, message)
+            },
+            Error::Rejected { service, message, .. } => {
+                write!(f, "{} rejected the request: {}", service, message)
+            },
+            Error::Parse { what, path, line, column, message } => {
+                write!(f, "could not parse {} at '{}' (line {}, column {}): {}",
+                       what, path, line, column, message)
+            },
+            Error::Io { what, message } => write!(f, "{}: {}", what, message),
+            Error::Planner { message } => write!(f, "planning failed: {}", message),
+            Error::GoalLayout { cell, path, message } => {
+                write!(f, "goal layout cell {} ({}): {}", cell, path, message)
+            },
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Error {
+        Error::Parse {
+            what: "json".to_string(),
+            path: String::new(),
+            line: error.line(),
+            column: error.column(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Error {
+        Error::io("io error", error)
+    }
+}
@@ -0,0 +1,226 @@
+use serde::Deserialize;
+use sp_domain::*;
+use crate::components::{model_variables, parse_value};
+use crate::config::Config;
+use crate::Error;
+
+/// One button in the demo goal grid.
+#[derive(Debug, Clone)]
+pub struct GoalCell {
+    pub path: SPPath,
+    pub label: String,
+    /// what clicking the button cycles through
+    pub values: Vec<SPValue>,
+}
+
+/// Dimensions, variables and values of the demo goal grid.
+#[derive(Debug, Clone, Default)]
+pub struct GoalLayout {
+    pub columns: usize,
+    pub cells: Vec<GoalCell>,
+    /// the model new intentions are added to
+    pub model_name: String,
+}
+
+/// A layout file, e.g.
+///
+/// ```toml
+/// columns = 3
+///
+/// [[cells]]
+/// path = "cell_2/product_state/in_1"
+/// label = "in 1"
+/// values = ["empty", "cylinder", "cube"]
+/// ```
+///
+/// `label` defaults to the last part of the path and `values` to the
+/// domain of the variable.
+#[derive(Debug, Clone, Deserialize)]
+struct LayoutFile {
+    columns: Option<usize>,
+    model: Option<String>,
+    cells: Vec<CellFile>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct CellFile {
+    path: String,
+    label: Option<String>,
+    values: Option<Vec<String>>,
+}
+
+/// Where the layout comes from.
+#[derive(Debug, Clone)]
+pub enum GoalLayoutSource {
+    File(String),
+    /// all model variables matching the pattern, `*` matches anything.
+    Pattern { pattern: String, columns: Option<usize> },
+}
+
+impl GoalLayoutSource {
+    pub fn from_config(config: &Config) -> Self {
+        match &config.goal_layout {
+            Some(path) => GoalLayoutSource::File(path.clone()),
+            None => GoalLayoutSource::Pattern {
+                pattern: config.goal_pattern.clone(),
+                columns: config.goal_columns,
+            },
+        }
+    }
+
+    pub fn layout(&self, model: &Model) -> Result<GoalLayout, Error> {
+        match self {
+            GoalLayoutSource::File(path) => {
+                let toml = std::fs::read_to_string(path)
+                    .map_err(|e| Error::io(&format!("could not read {}", path), e))?;
+                let file: LayoutFile = toml::from_str(&toml).map_err(|e| {
+                    let (line, column) = e.line_col().map(|(l, c)| (l + 1, c + 1)).unwrap_or((0, 0));
+                    Error::Parse {
+                        what: format!("goal layout {}", path),
+                        path: String::new(),
+                        line,
+                        column,
+                        message: e.to_string(),
+                    }
+                })?;
+                GoalLayout::from_file(file, model)
+            },
+            GoalLayoutSource::Pattern { pattern, columns } => {
+                // variables without a domain have nothing to cycle through.
+                let cells = model_variables(model)
+                    .filter(|v| wildcard_match(pattern, &v.path.to_string()))
+                    .filter_map(|v| default_values(v).map(|values| GoalCell {
+                        path: v.path.clone(),
+                        label: v.path.leaf(),
+                        values,
+                    }))
+                    .collect();
+                Ok(GoalLayout::new(cells, *columns, None))
+            },
+        }
+    }
+}
+
+impl GoalLayout {
+    fn new(cells: Vec<GoalCell>, columns: Option<usize>, model_name: Option<String>) -> Self {
+        // square-ish unless told otherwise.
+        let columns = columns.unwrap_or_else(|| (cells.len() as f64).sqrt().ceil() as usize).max(1);
+        let model_name = model_name
+            .or_else(|| cells.first().and_then(|c| c.path.path.first().cloned()))
+            .unwrap_or_default();
+        GoalLayout { columns, cells, model_name }
+    }
+
+    fn from_file(file: LayoutFile, model: &Model) -> Result<Self, Error> {
+        let mut cells = vec![];
+        for (i, c) in file.cells.into_iter().enumerate() {
+            let error = |message: &str| Error::GoalLayout {
+                cell: i + 1,
+                path: c.path.clone(),
+                message: message.to_string(),
+            };
+            let path = SPPath::from_string(&c.path);
+            let v = model_variables(model)
+                .find(|v| v.path == path)
+                .ok_or_else(|| error("no such variable in the model"))?;
+            let values = match &c.values {
+                Some(values) if values.is_empty() => return Err(error("no values given")),
+                Some(values) => values
+                    .iter()
+                    .map(|s| parse_value(s, v.value_type, &v.domain))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|message| error(&message))?,
+                None => default_values(v)
+                    .ok_or_else(|| error("the variable has no domain, list the values"))?,
+            };
+            cells.push(GoalCell {
+                label: c.label.unwrap_or_else(|| path.leaf()),
+                path,
+                values,
+            });
+        }
+        Ok(GoalLayout::new(cells, file.columns, file.model))
+    }
+}
+
+/// The domain, or both bools. None if there is nothing sensible to send.
+fn default_values(v: &Variable) -> Option<Vec<SPValue>> {
+    if !v.domain.is_empty() {
+        Some(v.domain.clone())
+    } else if v.value_type == SPValueType::Bool {
+        Some(vec![false.to_spvalue(), true.to_spvalue()])
+    } else {
+        None
+    }
+}
+
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == text,
+        Some((head, tail)) => {
+            text.starts_with(head) && {
+                let rest = &text[head.len()..];
+                (0..=rest.len())
+                    .filter(|i| rest.is_char_boundary(*i))
+                    .any(|i| wildcard_match(tail, &rest[i..]))
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_models;
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard_match("a/b", "a/b"));
+        assert!(!wildcard_match("a/b", "a/bc"));
+        assert!(wildcard_match("a/buffer_*", "a/buffer_1"));
+        assert!(wildcard_match("*/buffer_*", "cell/buffer_"));
+        assert!(wildcard_match("a/*/c", "a/x/y/c"));
+        assert!(!wildcard_match("a/*/c", "a/x/y/d"));
+        assert!(wildcard_match("*", ""));
+    }
+
+    #[test]
+    fn pattern_skips_variables_without_values() {
+        let mut model = test_models::model();
+        model.resources[0].variables.push(test_models::variable("r/count", SPValueType::Int32, vec![]));
+        let source = GoalLayoutSource::Pattern { pattern: "r/*".to_string(), columns: None };
+        let layout = source.layout(&model).unwrap();
+        let paths: Vec<_> = layout.cells.iter().map(|c| c.path.to_string()).collect();
+        assert_eq!(paths, vec!["r/b", "r/pos"]);
+        assert_eq!(layout.columns, 2);
+        assert_eq!(layout.model_name, "r");
+    }
+
+    fn cell(path: &str, values: Option<&[&str]>) -> CellFile {
+        CellFile {
+            path: path.to_string(),
+            label: None,
+            values: values.map(|vs| vs.iter().map(|v| v.to_string()).collect()),
+        }
+    }
+
+    fn from_cells(cells: Vec<CellFile>) -> Result<GoalLayout, Error> {
+        GoalLayout::from_file(LayoutFile { columns: None, model: None, cells }, &test_models::model())
+    }
+
+    #[test]
+    fn file_cells_use_listed_values_or_the_domain() {
+        let layout = from_cells(vec![cell("r/pos", Some(&["2", "0"])), cell("r/b", None)]).unwrap();
+        assert_eq!(layout.cells[0].values, vec![SPValue::Int32(2), SPValue::Int32(0)]);
+        assert_eq!(layout.cells[1].values, vec![SPValue::Bool(false), SPValue::Bool(true)]);
+        assert_eq!(layout.cells[1].label, "b");
+    }
+
+    #[test]
+    fn file_errors_name_the_cell() {
+        let e = from_cells(vec![cell("r/b", None), cell("r/missing", None)]).unwrap_err();
+        assert_eq!(e.to_string(), "goal layout cell 2 (r/missing): no such variable in the model");
+        let e = from_cells(vec![cell("r/pos", Some(&["7"]))]).unwrap_err();
+        assert_eq!(e.to_string(), "goal layout cell 1 (r/pos): 7 not in domain [0, 1, 2]");
+    }
+}
@@ -0,0 +1,188 @@
+use iced::{button, Button, Column, Element, Length, Row, Space, Text};
+use sp_domain::*;
+use crate::Message;
+
+/// A predicate evaluated against a state, broken down into its
+/// sub-predicates so we can see which part is false.
+#[derive(Debug, Clone)]
+pub struct GuardNode {
+    pub text: String,
+    pub value: bool,
+    pub children: Vec<GuardNode>,
+}
+
+impl GuardNode {
+    pub fn explain(p: &Predicate, state: &SPState) -> GuardNode {
+        let (text, children) = match p {
+            Predicate::AND(ps) => ("AND".to_string(), ps.iter().map(|p| GuardNode::explain(p, state)).collect()),
+            Predicate::OR(ps) => ("OR".to_string(), ps.iter().map(|p| GuardNode::explain(p, state)).collect()),
+            Predicate::NOT(p) => ("NOT".to_string(), vec![GuardNode::explain(p, state)]),
+            Predicate::EQ(a, b) | Predicate::NEQ(a, b) => {
+                let values: Vec<_> = [a, b].iter().flat_map(|pv| current_value(pv, state)).collect();
+                if values.is_empty() {
+                    (p.to_string(), vec![])
+                } else {
+                    (format!("{}    ({})", p, values.join(", ")), vec![])
+                }
+            },
+            _ => (p.to_string(), vec![]),
+        };
+
+        GuardNode {
+            text,
+            value: p.eval(state),
+            children,
+        }
+    }
+
+    /// Number of parts that make the predicate false: the false leaves
+    /// under a false AND or OR, or a NOT whose child holds.
+    pub fn failing(&self) -> usize {
+        if self.value {
+            0
+        } else if self.children.is_empty() || self.text == "NOT" {
+            1
+        } else {
+            self.children.iter().map(|c| c.failing()).sum()
+        }
+    }
+}
+
+fn current_value(pv: &PredicateValue, state: &SPState) -> Option<String> {
+    match pv {
+        PredicateValue::SPPath(path, _) => {
+            let value = state.sp_value_from_path(path)
+                .map(|v| v.to_string())
+                .unwrap_or("[no state]".into());
+            Some(format!("{} = {}", path, value))
+        },
+        _ => None,
+    }
+}
+
+pub(crate) fn view_guard_tree<'a>(node: &GuardNode, depth: usize) -> Column<'a, Message> {
+    let color = if node.value {
+        [0.0, 0.5, 0.0]
+    } else {
+        [0.8, 0.1, 0.1]
+    };
+    let row = Row::new()
+        .spacing(10)
+        .push(Space::with_width(Length::Units((depth * 20) as u16)))
+        .push(Text::new(if node.value { "true " } else { "false" }).size(14).color(color))
+        .push(Text::new(&node.text).size(14).color(color));
+
+    node.children
+        .iter()
+        .fold(Column::new().spacing(2).push(row), |col, c| {
+            col.push(view_guard_tree(c, depth + 1))
+        })
+}
+
+pub(crate) fn view_transition_guards<'a>(t: &Transition, state: &SPState) -> Column<'a, Message> {
+    let guard = GuardNode::explain(&t.guard, state);
+    let runner_guard = GuardNode::explain(&t.runner_guard, state);
+    Column::new()
+        .spacing(5)
+        .push(guard_heading("guard", &guard))
+        .push(view_guard_tree(&guard, 1))
+        .push(guard_heading("runner guard", &runner_guard))
+        .push(view_guard_tree(&runner_guard, 1))
+}
+
+/// The heading says how many parts of the guard are false.
+fn guard_heading(title: &str, node: &GuardNode) -> Text {
+    match node.failing() {
+        0 => Text::new(title.to_string()).size(16),
+        n => Text::new(format!("{} ({} failing)", title, n)).size(16).color([0.8, 0.1, 0.1]),
+    }
+}
+
+/// Guards, actions and effects of a transition, for the expanded rows of the plan view.
+pub(crate) fn view_transition_detail<'a>(t: &Transition, state: &SPState) -> Element<'a, Message> {
+    let actions = t.actions
+        .iter()
+        .fold(Column::new().spacing(2).push(Text::new("actions").size(16)), |col, a| {
+            col.push(Text::new(format!("    {}", a)).size(14))
+        });
+    let runner_actions = t.runner_actions
+        .iter()
+        .fold(Column::new().spacing(2).push(Text::new("runner actions").size(16)), |col, a| {
+            col.push(Text::new(format!("    {}", a)).size(14))
+        });
+
+    Row::new()
+        .push(Space::with_width(Length::Units(40)))
+        .push(Column::new()
+              .spacing(5)
+              .push(view_transition_guards(t, state))
+              .push(actions)
+              .push(runner_actions))
+        .into()
+}
+
+pub(crate) fn view_operation_detail<'a>(op: &Operation, state: &SPState,
+                                        close_button: &'a mut button::State) -> Element<'a, Message> {
+    Column::new()
+        .spacing(10)
+        .padding(10)
+        .push(Row::new()
+              .spacing(20)
+              .push(Text::new(op.path.to_string()).size(24).width(Length::Fill))
+              .push(Button::new(close_button, Text::new("Close").size(10))
+                    .padding(10)
+                    .on_press(Message::SelectOperation(None))))
+        .push(Text::new("Precondition").size(20))
+        .push(view_transition_guards(&op.start, state))
+        .push(Text::new("Postcondition").size(20))
+        .push(view_transition_guards(&op.finish, state))
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_models;
+
+    fn eq(path: &str, value: i32) -> Predicate {
+        Predicate::EQ(PredicateValue::SPPath(test_models::path(path), None),
+                      PredicateValue::SPValue(SPValue::Int32(value)))
+    }
+
+    fn state() -> SPState {
+        SPState::new_from_values(&[(test_models::path("x"), SPValue::Int32(0)),
+                                   (test_models::path("y"), SPValue::Int32(1))])
+    }
+
+    #[test]
+    fn explain_keeps_the_structure_and_shows_values() {
+        let node = GuardNode::explain(&Predicate::AND(vec![eq("x", 0), eq("y", 0)]), &state());
+        assert_eq!((node.text.as_str(), node.value), ("AND", false));
+        let children: Vec<_> = node.children.iter().map(|c| c.value).collect();
+        assert_eq!(children, vec![true, false]);
+        assert!(node.children[1].text.ends_with("(y = 1)"));
+    }
+
+    #[test]
+    fn failing_counts_the_false_leaves_of_and() {
+        let p = Predicate::AND(vec![eq("x", 1), eq("y", 1), eq("y", 0)]);
+        assert_eq!(GuardNode::explain(&p, &state()).failing(), 2);
+    }
+
+    #[test]
+    fn true_guards_have_nothing_failing() {
+        let or = Predicate::OR(vec![eq("x", 0), eq("x", 1)]);
+        assert_eq!(GuardNode::explain(&or, &state()).failing(), 0);
+        let not = Predicate::NOT(Box::new(eq("x", 1)));
+        assert_eq!(GuardNode::explain(&not, &state()).failing(), 0);
+    }
+
+    #[test]
+    fn false_or_and_not() {
+        let or = Predicate::OR(vec![eq("x", 1), eq("y", 0)]);
+        assert_eq!(GuardNode::explain(&or, &state()).failing(), 2);
+        // the NOT itself fails, not the leaf under it that holds.
+        let not = Predicate::AND(vec![Predicate::NOT(Box::new(eq("x", 0))), eq("y", 1)]);
+        assert_eq!(GuardNode::explain(&not, &state()).failing(), 1);
+    }
+}
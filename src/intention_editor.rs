@@ -0,0 +1,173 @@
+use iced::{button, scrollable, text_input, Alignment, Button, Checkbox, Column, Element, Length, Row,
+           Scrollable, Text, TextInput};
+use sp_domain::*;
+use std::collections::HashMap;
+use crate::predicate_editor::{variables_of, PredicateEdit, PredicateEditor};
+use crate::Message;
+
+#[derive(Debug, Clone)]
+pub enum IntentionEdit {
+    Name(String),
+    AutoRun(bool),
+    Pre(PredicateEdit),
+    Post(PredicateEdit),
+    Check,
+    Send,
+}
+
+/// Builds new intentions from pre- and postconditions over the model variables.
+#[derive(Debug)]
+pub struct IntentionEditor {
+    /// the model the intention is added to
+    pub model_name: String,
+    pub name: String,
+    pub auto_run: bool,
+    /// result of the last reachability check
+    pub check: Option<String>,
+    pre: PredicateEditor,
+    post: PredicateEditor,
+    variables: HashMap<SPPath, Variable>,
+    variable_names: Vec<String>,
+
+    name_state: text_input::State,
+    check_button: button::State,
+    send_button: button::State,
+    scroll: scrollable::State,
+}
+
+impl IntentionEditor {
+    pub fn new(model: &Model) -> Self {
+        let (variables, variable_names) = variables_of(model);
+        IntentionEditor {
+            model_name: model_name(&variables),
+            name: "new_intention".to_string(),
+            auto_run: false,
+            check: None,
+            pre: PredicateEditor::new("TRUE"),
+            post: PredicateEditor::new("AND"),
+            variables,
+            variable_names,
+            name_state: text_input::State::new(),
+            check_button: button::State::new(),
+            send_button: button::State::new(),
+            scroll: scrollable::State::new(),
+        }
+    }
+
+    /// Resolve the variables against a reloaded model, keeping the edits.
+    /// Conditions on variables that are gone show up as errors in `build`.
+    pub fn set_model(&mut self, model: &Model) {
+        let (variables, variable_names) = variables_of(model);
+        self.model_name = model_name(&variables);
+        self.variables = variables;
+        self.variable_names = variable_names;
+        self.check = None;
+    }
+
+    pub fn update(&mut self, edit: IntentionEdit) {
+        // the old check says nothing about the edited goal.
+        self.check = None;
+        match edit {
+            IntentionEdit::Name(name) => self.name = name,
+            IntentionEdit::AutoRun(auto_run) => self.auto_run = auto_run,
+            IntentionEdit::Pre(edit) => self.pre.update(edit),
+            IntentionEdit::Post(edit) => self.post.update(edit),
+            IntentionEdit::Check | IntentionEdit::Send => (),
+        }
+    }
+
+    /// The pre- and postcondition, or what is missing.
+    pub fn build(&self) -> Result<(Predicate, Predicate), String> {
+        if self.name.trim().is_empty() {
+            return Err("the intention needs a name".to_string());
+        }
+        let pre = self.pre.build(&self.variables)?;
+        let post = self.post.build(&self.variables)?;
+        Ok((pre, post))
+    }
+
+    pub fn view(&mut self, read_only: bool) -> Element<Message> {
+        let preview = match self.build() {
+            Ok((pre, post)) => Text::new(format!("pre: {}\npost: {}", pre, post)).size(14),
+            Err(e) => Text::new(e).size(14).color([0.8, 0.1, 0.1]),
+        };
+        let valid = self.build().is_ok();
+        let can_send = !read_only && valid;
+        let check = Text::new(self.check.clone().unwrap_or_default()).size(14);
+
+        let header = Row::new()
+            .spacing(20)
+            .align_items(Alignment::Center)
+            .push(TextInput::new(&mut self.name_state, "name", &self.name,
+                                 |s| Message::IntentionEdit(IntentionEdit::Name(s)))
+                  .padding(5))
+            .push(Checkbox::new(self.auto_run, "Auto run",
+                                |b| Message::IntentionEdit(IntentionEdit::AutoRun(b))));
+        let send = Button::new(&mut self.send_button, Text::new("Send intention"))
+            .padding(8)
+            .style(crate::style::Button::Primary);
+        let send = if can_send {
+            send.on_press(Message::IntentionEdit(IntentionEdit::Send))
+        } else {
+            send
+        };
+        let check_button = Button::new(&mut self.check_button, Text::new("Check reachability"))
+            .padding(8);
+        let check_button = if valid {
+            check_button.on_press(Message::IntentionEdit(IntentionEdit::Check))
+        } else {
+            check_button
+        };
+
+        let content = Column::new()
+            .spacing(10)
+            .push(header)
+            .push(Text::new("Precondition").size(20))
+            .push(self.pre.view(|e| Message::IntentionEdit(IntentionEdit::Pre(e)),
+                                &self.variable_names, &self.variables))
+            .push(Text::new("Postcondition").size(20))
+            .push(self.post.view(|e| Message::IntentionEdit(IntentionEdit::Post(e)),
+                                 &self.variable_names, &self.variables))
+            .push(preview)
+            .push(check)
+            .push(Row::new()
+                  .spacing(10)
+                  .push(check_button)
+                  .push(send));
+
+        Scrollable::new(&mut self.scroll)
+            .height(Length::Fill)
+            .push(content)
+            .into()
+    }
+}
+
+/// Variables live under the model name, e.g. lab_scenario_1/product_state/buffer_1.
+fn model_name(variables: &HashMap<SPPath, Variable>) -> String {
+    variables.keys()
+        .filter_map(|p| p.path.first().cloned())
+        .min()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_models;
+
+    #[test]
+    fn edits_survive_a_reload() {
+        let mut e = IntentionEditor::new(&test_models::model());
+        e.update(IntentionEdit::Name("to_home".into()));
+        e.update(IntentionEdit::Post(PredicateEdit::AddChild(0)));
+        e.update(IntentionEdit::Post(PredicateEdit::Variable(1, "r/pos".into())));
+        e.update(IntentionEdit::Post(PredicateEdit::Value(1, "2".into())));
+        assert!(e.build().is_ok());
+
+        let mut model = test_models::model();
+        model.resources[0].variables.retain(|v| v.path != test_models::path("r/pos"));
+        e.set_model(&model);
+        assert_eq!(e.name, "to_home");
+        assert_eq!(e.build(), Err("r/pos is not in the model".to_string()));
+    }
+}
@@ -1,47 +1,121 @@
-use futures::future;
-use futures::stream::StreamExt;
 use iced::{
     window, button, scrollable, text_input, Alignment, Application, Button, Column, Command, Container, Element, Length, Row, Settings, Subscription, Text, TextInput
 };
 use iced_native::subscription;
-use r2r;
-use serde::Deserialize;
 use sp_domain::*;
-use sp_formal::CompiledModel;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+mod backend;
+use backend::*;
+mod config;
+use config::Config;
+mod error;
+use error::Error;
+mod notifications;
+use notifications::Notifications;
 mod components;
 use components::*;
+mod state_tree;
+mod state_store;
+mod state_channel;
+use state_channel::StateReceiver;
+mod guards;
+mod recorder;
+use recorder::*;
+mod status;
+use status::*;
+mod audit;
+use audit::*;
+mod confirm;
+use confirm::{Confirmation, Pending};
+mod goal_layout;
+use goal_layout::GoalLayoutSource;
+mod intention_editor;
+use intention_editor::*;
+mod planning;
+use planning::Plan;
+mod predicate_editor;
+mod what_if;
+use what_if::*;
+mod model_browser;
+mod model_diff;
+use model_diff::ModelDiff;
+#[cfg(test)]
+mod test_models;
 
 pub fn main() -> iced::Result {
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let size = (config.window_width, config.window_height);
+
     SPOpViewer::run(
         Settings {
             antialiasing: true,
             window: window::Settings {
                 position: window::Position::Centered,
-                size: (750, 720),
+                size,
                 ..window::Settings::default()
             },
-            ..Settings::default()
+            ..Settings::with_flags(config)
         })
 }
 
 struct SPOpViewer {
-    // ros communication stuff
-    get_model_client: Arc<Mutex<r2r::Client<r2r::sp_msgs::srv::Json::Service>>>,
-    set_model_client: Arc<Mutex<r2r::Client<r2r::sp_msgs::srv::Json::Service>>>,
-    set_state_client: Arc<Mutex<r2r::Client<r2r::sp_msgs::srv::Json::Service>>>,
-    new_state_receiver: Mutex<Option<tokio::sync::mpsc::Receiver<SPState>>>,
+    // communication with the runner
+    backend: Arc<dyn Backend>,
+    new_state_receiver: Mutex<Option<StateReceiver>>,
 
     // our ui state
     ui_state: SPOpViewerState,
 
+    // connection health
+    status: ConnectionStatus,
+    model_retries: u32,
+    retry_at: Option<Instant>,
+
     // other state...
-    notification: Option<Notification>,
+    notifications: Notifications,
+    recording: Recording,
+    replay: Option<Replay>,
 
     // global filter textbox
     filter_string: String,
     filter_edit_state: text_input::State,
+    audit: Arc<Mutex<AuditLog>>,
+    audit_view: AuditView,
+    confirmation: Option<Confirmation>,
+    read_only: bool,
+    goal_layout: GoalLayoutSource,
+    intention_editor: Option<IntentionEditor>,
+    what_if: Option<WhatIfPlanner>,
+    plan_max_steps: u32,
+    /// the ui of the old model while a new one is loading
+    kept: Option<KeptView>,
+    model_diff: Option<ModelDiff>,
+}
+
+/// What survives a model reload.
+struct KeptView {
+    model_info: SPModelInfo,
+    current_view: View,
+    scroll: scrollable::State,
+    footer: Footer,
+    intention_editor: Option<IntentionEditor>,
+    what_if: Option<WhatIfPlanner>,
+}
+
+/// An intention waiting for the reachability check before it is sent.
+#[derive(Debug, Clone)]
+pub struct PendingIntention {
+    model: Model,
+    post: Predicate,
+    cause: String,
 }
 
 #[derive(Debug, Clone)]
@@ -52,6 +126,13 @@ pub enum View {
     OPlanView,
     StateView,
     DemoGoalView,
+    RecordingView,
+    NotificationView,
+    AuditView,
+    IntentionEditorView,
+    WhatIfView,
+    ModelView,
+    ModelDiffView,
 }
 
 #[derive(Debug, Clone)]
@@ -65,6 +146,7 @@ enum SPOpViewerState {
     },
     Errored {
         get_model_button: button::State,
+        error: Error,
     },
 }
 
@@ -77,12 +159,26 @@ struct Footer {
     state_view_button: button::State,
     get_model_button: button::State,
     make_goal_button: button::State,
+    intention_editor_button: button::State,
+    what_if_button: button::State,
+    model_view_button: button::State,
+    model_diff_button: button::State,
+    record_view_button: button::State,
+    notification_view_button: button::State,
+    audit_view_button: button::State,
 }
 
 impl Footer {
-    fn view(&mut self) -> Element<Message> {
-        Row::new()
-            .spacing(20)
+    fn view(&mut self, read_only: bool) -> Element<Message> {
+        let row = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center);
+        let row = if read_only {
+            row.push(Text::new("READ-ONLY").size(14).color([0.8, 0.1, 0.1]))
+        } else {
+            row
+        };
+        row
             .push(button(&mut self.int_view_button, "Intentions")
                   .on_press(Message::ChangeView(View::IntentionView)))
             .push(button(&mut self.oplan_view_button, "O. Plan")
@@ -95,13 +191,27 @@ impl Footer {
                   .on_press(Message::ChangeView(View::StateView)))
             .push(button(&mut self.make_goal_button, "Make goal")
                   .on_press(Message::ChangeView(View::DemoGoalView)))
+            .push(button(&mut self.intention_editor_button, "New intention")
+                  .on_press(Message::ChangeView(View::IntentionEditorView)))
+            .push(button(&mut self.what_if_button, "What if")
+                  .on_press(Message::ChangeView(View::WhatIfView)))
+            .push(button(&mut self.model_view_button, "Model")
+                  .on_press(Message::ChangeView(View::ModelView)))
+            .push(button(&mut self.model_diff_button, "Changes")
+                  .on_press(Message::ChangeView(View::ModelDiffView)))
+            .push(button(&mut self.record_view_button, "Record")
+                  .on_press(Message::ChangeView(View::RecordingView)))
+            .push(button(&mut self.notification_view_button, "Log")
+                  .on_press(Message::ChangeView(View::NotificationView)))
+            .push(button(&mut self.audit_view_button, "Audit")
+                  .on_press(Message::ChangeView(View::AuditView)))
             .push(button(&mut self.get_model_button, "Get sp model")
                   .on_press(Message::UpdateModel))
             .into()
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub enum BufferLocationType {
     Estimated,
     Goal,
@@ -112,137 +222,112 @@ pub enum Message {
     Empty, // hmmm
     ChangeView(View),
     ModelUpdate(Result<SPModelInfo, Error>),
-    BufferButton(BufferLocationType, usize, bool),
+    BufferButton(BufferLocationType, usize, usize),
+    StateReceived(SPState),
+    StateParseError(String, String),
     NewState(SPState),
     StateValueEdit(SPPath, String),
+    StateValueCommit(SPPath),
+    StateValueSet(SPPath, String),
+    ToggleStateGroup(SPPath),
+    SelectOperation(Option<SPPath>),
+    ToggleTransitionDetail(String),
+    ApplyStateEdits,
     UpdateModel,
+    RetryModel(u32),
+    StatusTick,
+    ServicesChecked(ServiceStatus),
+    AskResetOperation(SPPath, SPValue),
+    Confirm,
+    CancelConfirmation,
     ResetOperation(SPPath, SPValue),
     SetEstimatedCylinders,
     SendGoalCylinders,
     SetNotification(String, NotificationType),
-    ClearNotification,
+    ClearNotification(u64),
+    NotificationFilter(NotificationType, bool),
+    NotificationExportPathChanged(String),
+    ExportNotifications,
     FilterChanged(String),
+    RecordPathChanged(String),
+    ToggleRecording,
+    ReplayPathChanged(String),
+    StartReplay,
+    StopReplay,
+    ReplayTick,
+    ReplayPlayPause,
+    ReplaySpeed(f32),
+    ReplaySeek(usize),
+    ToggleAuditEntry(usize),
+    IntentionEdit(IntentionEdit),
+    IntentionChecked(Result<Plan, Error>),
+    GoalChecked(PendingIntention, Result<Plan, Error>),
+    WhatIf(WhatIfEdit),
+    WhatIfPlanned(SPState, Result<Plan, Error>),
+    ToggleModelSection(SPPath),
+    ShowInState(SPPath),
 }
 
-async fn set_state(
-    client: Arc<Mutex<r2r::Client<r2r::sp_msgs::srv::Json::Service>>>,
-    json: String,
-) -> Result<(), Error> {
-    let req_msg = r2r::sp_msgs::srv::Json::Request { json: json.clone() };
-    let req = client.lock().unwrap().request(&req_msg)?;
-    let resp = tokio::time::timeout(std::time::Duration::from_millis(500), req).await;
-    if resp.is_err() {
-        println!("state change request timed out: {}", json);
-    }
-    Ok(())
+/// `cause` is what ends up in the audit log as the reason for the command.
+async fn set_state(backend: Arc<dyn Backend>, audit: Arc<Mutex<AuditLog>>,
+                   cause: String, json: String) -> Result<(), Error> {
+    let index = audit.lock().unwrap().sent(&cause, "set_state", &json);
+    let result = backend.set_state(json).await;
+    audit.lock().unwrap().answered(index, &result);
+    result.map(|_| ())
 }
 
-async fn get_model(
-    client: Arc<Mutex<r2r::Client<r2r::sp_msgs::srv::Json::Service>>>,
-) -> Result<SPModelInfo, Error> {
-    let req_msg = r2r::sp_msgs::srv::Json::Request::default();
-
-    // jsut for testing
-    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-
-    let req = client.lock().unwrap().request(&req_msg)?;
-    let resp = tokio::time::timeout(std::time::Duration::from_secs(1), req).await;
-    if let Ok(Ok(msg)) = resp {
-        #[derive(Debug, PartialEq, Clone, Default, Deserialize)]
-        pub struct RunnerModel {
-            pub compiled_model: CompiledModel,
-            pub changes: Option<sp_domain::Model>,
-        }
-
-        let rm: RunnerModel = serde_json::from_str(&msg.json)?;
-        Ok(SPModelInfo::from(rm.compiled_model))
-    } else {
-        return Err(Error::RosError);
-    }
+async fn get_model(backend: Arc<dyn Backend>) -> Result<SPModelInfo, Error> {
+    let compiled_model = backend.get_model().await?;
+    Ok(SPModelInfo::from(compiled_model))
 }
 
-async fn set_model(
-    client: Arc<Mutex<r2r::Client<r2r::sp_msgs::srv::Json::Service>>>,
-    model: Model,
-) -> Result<(), Error> {
-    // let req_msg = r2r::sp_msgs::srv::Json::Request::default();
-    let json = serde_json::to_string(&model).expect("could not serialize");
-    let req_msg = r2r::sp_msgs::srv::Json::Request { json };
-
-    let req = client.lock().unwrap().request(&req_msg)?;
-    let resp = tokio::time::timeout(std::time::Duration::from_secs(1), req).await;
-    if let Ok(Ok(msg)) = resp {
-        Ok(())
-    } else {
-        return Err(Error::RosError);
-    }
+async fn set_model(backend: Arc<dyn Backend>, audit: Arc<Mutex<AuditLog>>,
+                   cause: String, model: Model) -> Result<(), Error> {
+    let json = serde_json::to_string(&model)?;
+    let index = audit.lock().unwrap().sent(&cause, "set_model", &json);
+    let result = backend.set_model(json).await;
+    audit.lock().unwrap().answered(index, &result);
+    result.map(|_| ())
 }
 
 impl Application for SPOpViewer {
     type Executor = iced::executor::Default;
     type Message = Message;
-    type Flags = ();
-
-    fn new(_flags: ()) -> (SPOpViewer, Command<Message>) {
-        let ctx = r2r::Context::create().expect("could not create context");
-        let mut node = r2r::Node::create(ctx, "sp_op_viewer", "").expect("...");
-        let get_model_client = Arc::new(Mutex::new(
-            node.create_client::<r2r::sp_msgs::srv::Json::Service>("/sp/get_model")
-                .expect("could not create client"),
-        ));
-        let set_model_client = Arc::new(Mutex::new(
-            node.create_client::<r2r::sp_msgs::srv::Json::Service>("/sp/set_model")
-                .expect("could not create client"),
-        ));
-        let set_state_client = Arc::new(Mutex::new(
-            node.create_client::<r2r::sp_msgs::srv::Json::Service>("/sp/set_state")
-                .expect("could not create client"),
-        ));
-        let sub = node
-            .subscribe::<r2r::std_msgs::msg::String>("/sp/state_flat", r2r::QosProfile::default())
-            .expect("could not subscribe");
-
-        let _handle = std::thread::spawn(move || loop {
-            node.spin_once(std::time::Duration::from_millis(100));
-        });
-
-        let (sender, receiver) = tokio::sync::mpsc::channel::<SPState>(1);
-        let _sub = tokio::spawn(async move {
-            sub.for_each(|msg| {
-                let s: SPStateJson = serde_json::from_str(&msg.data).expect("could not parse");
-                let _res = sender.try_send(s.to_state());
-                future::ready(())
-            })
-            .await;
-        });
+    type Flags = Config;
 
-        (
-            SPOpViewer {
-                new_state_receiver: Mutex::new(Some(receiver)),
-                get_model_client: get_model_client.clone(),
-                set_model_client: set_model_client.clone(),
-                set_state_client: set_state_client.clone(),
-                ui_state: SPOpViewerState::Loading,
-                notification: None,
-                filter_string: String::new(),
-                filter_edit_state: text_input::State::new(),
-            },
-            Command::perform(get_model(get_model_client), Message::ModelUpdate),
-        )
+    fn new(config: Config) -> (SPOpViewer, Command<Message>) {
+        // run against an in-memory model instead of ros if asked to.
+        let backend: Arc<dyn Backend> = match &config.mock_model {
+            Some(path) => Arc::new(MockBackend::from_file(path, &config).expect("could not load mock model")),
+            None => Arc::new(RosBackend::new(&config)),
+        };
+        SPOpViewer::with_backend(backend, &config)
     }
 
     fn subscription(&self) -> Subscription<Message> {
-        if let Some(r) = self.new_state_receiver.lock().unwrap().take() {
+        let states = if let Some(r) = self.new_state_receiver.lock().unwrap().take() {
             subscription::unfold(1, r, |mut r| async move {
-                if let Some(new_state) = r.recv().await {
-                    (Some(Message::NewState(new_state)), r)
-                } else {
-                    (None, r)
+                match r.recv().await {
+                    Some(StateUpdate::State(new_state)) => (Some(Message::StateReceived(new_state)), r),
+                    Some(StateUpdate::ParseError(e, payload)) => (Some(Message::StateParseError(e, payload)), r),
+                    None => (None, r),
                 }
             })
         } else {
             subscription::unfold(1, (), |_| async move { (None, ()) })
+        };
+
+        let mut subscriptions = vec![
+            states,
+            iced::time::every(Duration::from_secs(1)).map(|_| Message::StatusTick),
+        ];
+        match &self.replay {
+            Some(replay) if replay.is_playing() => subscriptions.push(
+                iced::time::every(Duration::from_millis(50)).map(|_| Message::ReplayTick)),
+            _ => (),
         }
+        Subscription::batch(subscriptions)
     }
 
     fn title(&self) -> String {
@@ -256,19 +341,62 @@ impl Application for SPOpViewer {
     }
 
     fn update(&mut self, message: Message) -> Command<Message> {
+        if self.read_only && message.writes() {
+            return Command::none();
+        }
         match message {
             Message::Empty => Command::none(),
+            Message::AskResetOperation(path, change_to) => {
+                let current = match &self.ui_state {
+                    SPOpViewerState::Loaded { model_info, .. } => model_info.state.value(&path).cloned(),
+                    _ => None,
+                };
+                self.confirmation = Some(Confirmation::new(Pending::StateChange {
+                    path,
+                    current,
+                    new_value: change_to,
+                }));
+                Command::none()
+            },
+            Message::Confirm => match self.confirmation.take().map(|c| c.pending) {
+                Some(Pending::StateChange { path, new_value, .. }) => {
+                    self.update(Message::ResetOperation(path, new_value))
+                },
+                Some(Pending::Intention { pending, .. }) => {
+                    let cause = format!("{}, sent anyway", pending.cause);
+                    self.send_intention(pending.model, pending.post, cause)
+                },
+                None => Command::none(),
+            },
+            Message::CancelConfirmation => {
+                self.confirmation = None;
+                Command::none()
+            },
             Message::SetNotification(msg, t) => {
-                self.notification = Some(Notification::new(msg, t));
-                Command::perform(
-                    tokio::time::sleep(std::time::Duration::from_millis(2500)), |_| {
-                    Message::ClearNotification
+                let (id, timeout) = self.notifications.push(msg, t);
+                Command::perform(tokio::time::sleep(timeout), move |_| {
+                    Message::ClearNotification(id)
                 })
             }
-            Message::ClearNotification => {
-                self.notification = None;
+            Message::ClearNotification(id) => {
+                self.notifications.remove(id);
                 Command::none()
             },
+            Message::NotificationFilter(t, show) => {
+                self.notifications.set_filter(t, show);
+                Command::none()
+            },
+            Message::NotificationExportPathChanged(path) => {
+                self.notifications.export_path = path;
+                Command::none()
+            },
+            Message::ExportNotifications => match self.notifications.export() {
+                Ok(count) => self.update(Message::SetNotification(
+                    format!("Exported {} notifications to {}", count, self.notifications.export_path),
+                    NotificationType::Neutral)),
+                Err(e) => self.update(Message::SetNotification(
+                    format!("Export failed: {}", e), NotificationType::Sad)),
+            },
             Message::FilterChanged(s) => {
                 self.filter_string = s;
                 Command::none()
@@ -301,14 +429,72 @@ impl Application for SPOpViewer {
                     footer: _,
                 } = &mut self.ui_state
                 {
-                    match model_info.state.iter_mut().find(|si| si.path == path) {
-                        // TODO use the model to look up the correct type.
-                        Some(ref mut si) => si.new_value = value,
-                        None => (),
+                    if let Some(si) = model_info.state.get_mut(&path) {
+                        si.new_value = value;
+                        si.edit_error = None;
                     }
                 }
                 Command::none()
             }
+            Message::StateValueCommit(path) => {
+                let cause = format!("StateValueCommit({})", path);
+                self.commit_state_edits(Some(&path), cause)
+            },
+            Message::StateValueSet(path, value) => {
+                // toggles and pick lists send right away.
+                if let SPOpViewerState::Loaded { model_info, .. } = &mut self.ui_state {
+                    if let Some(si) = model_info.state.get_mut(&path) {
+                        si.new_value = value;
+                        si.edit_error = None;
+                    }
+                }
+                let cause = format!("StateValueSet({})", path);
+                self.commit_state_edits(Some(&path), cause)
+            },
+            Message::ApplyStateEdits => self.commit_state_edits(None, "ApplyStateEdits".to_string()),
+            Message::ToggleAuditEntry(index) => {
+                self.audit_view.toggle(index);
+                Command::none()
+            },
+            Message::ToggleStateGroup(path) => {
+                if let SPOpViewerState::Loaded { model_info, .. } = &mut self.ui_state {
+                    if !model_info.expanded.remove(&path) {
+                        model_info.expanded.insert(path);
+                    }
+                }
+                Command::none()
+            }
+            Message::SelectOperation(path) => {
+                if let SPOpViewerState::Loaded { model_info, .. } = &mut self.ui_state {
+                    model_info.selected_operation = path;
+                }
+                Command::none()
+            }
+            Message::ToggleTransitionDetail(path) => {
+                if let SPOpViewerState::Loaded { model_info, .. } = &mut self.ui_state {
+                    if !model_info.expanded_transitions.remove(&path) {
+                        model_info.expanded_transitions.insert(path);
+                    }
+                }
+                Command::none()
+            }
+            Message::ToggleModelSection(path) => {
+                if let SPOpViewerState::Loaded { model_info, .. } = &mut self.ui_state {
+                    model_info.browser.toggle(path);
+                }
+                Command::none()
+            },
+            Message::ShowInState(path) => {
+                if let SPOpViewerState::Loaded { model_info, current_view, .. } = &mut self.ui_state {
+                    // open the groups down to the variable and filter on it.
+                    for len in 1..path.path.len() {
+                        model_info.expanded.insert(state_tree::prefix(&path, len));
+                    }
+                    self.filter_string = path.to_string();
+                    *current_view = View::StateView;
+                }
+                Command::none()
+            },
             Message::ChangeView(view) => {
                 if let SPOpViewerState::Loaded {
                     model_info: _,
@@ -321,30 +507,167 @@ impl Application for SPOpViewer {
                 }
                 Command::none()
             }
-            Message::ModelUpdate(Ok(model_info)) => {
+            Message::ModelUpdate(Ok(mut model_info)) => {
+                self.model_retries = 0;
+                self.retry_at = None;
+                let layout = self.goal_layout.layout(&model_info.compiled_model.model);
+                let layout_error = layout.as_ref().err().map(|e| e.to_string());
+                model_info.set_goal_layout(layout.unwrap_or_default());
+                let kept = self.kept.take();
+                self.model_diff = kept.as_ref()
+                    .map(|k| ModelDiff::new(&k.model_info.compiled_model.model, &model_info.compiled_model.model));
+                let loaded_message = match &self.model_diff {
+                    Some(diff) if !diff.changes.is_empty() =>
+                        format!("Model loaded, {} changes (see Changes)", diff.changes.len()),
+                    _ => "Model loaded!".to_string(),
+                };
+                let model = &model_info.compiled_model.model;
+                let (current_view, scroll, footer) = match kept {
+                    Some(k) => {
+                        let mut intention_editor = k.intention_editor.unwrap_or_else(|| IntentionEditor::new(model));
+                        intention_editor.set_model(model);
+                        let mut what_if = k.what_if.unwrap_or_else(|| WhatIfPlanner::new(model));
+                        what_if.set_model(model);
+                        self.intention_editor = Some(intention_editor);
+                        self.what_if = Some(what_if);
+                        model_info.keep_from(k.model_info);
+                        (k.current_view, k.scroll, k.footer)
+                    },
+                    None => {
+                        self.intention_editor = Some(IntentionEditor::new(model));
+                        self.what_if = Some(WhatIfPlanner::new(model));
+                        (View::StateView, scrollable::State::new(), Footer::default())
+                    },
+                };
                 self.ui_state = SPOpViewerState::Loaded {
                     model_info,
-                    current_view: View::StateView,
-                    scroll: scrollable::State::new(),
-                    footer: Footer::default(),
+                    current_view,
+                    scroll,
+                    footer,
                 };
 
-                // hack... dont know how to send again here
-                self.notification = Some(Notification::new("Model loaded!".to_string(),
-                                                           NotificationType::Happy));
-                Command::perform(
-                    tokio::time::sleep(std::time::Duration::from_millis(2500)), |_| {
-                    Message::ClearNotification
-                })
-
+                let loaded = self.update(Message::SetNotification(loaded_message, NotificationType::Happy));
+                match layout_error {
+                    Some(e) => Command::batch(vec![loaded, self.update(Message::SetNotification(
+                        format!("Could not build the goal grid: {}", e), NotificationType::Sad))]),
+                    None => loaded,
+                }
             }
-            Message::ModelUpdate(Err(_error)) => {
+            Message::ModelUpdate(Err(error)) => {
                 self.ui_state = SPOpViewerState::Errored {
                     get_model_button: button::State::new(),
+                    error,
                 };
 
+                // retry with exponential backoff, capped at 30 seconds.
+                self.model_retries += 1;
+                let delay = Duration::from_secs(2u64.pow(self.model_retries.min(6) - 1).min(30));
+                self.retry_at = Some(Instant::now() + delay);
+                let attempt = self.model_retries;
+                Command::perform(tokio::time::sleep(delay), move |_| Message::RetryModel(attempt))
+            }
+            Message::RetryModel(attempt) => match self.ui_state {
+                // only the latest scheduled retry counts, and only while still failing.
+                SPOpViewerState::Errored { .. } if attempt == self.model_retries => {
+                    self.update(Message::UpdateModel)
+                },
+                _ => Command::none(),
+            },
+            Message::StatusTick => {
+                let check = Command::perform(self.backend.service_status(), Message::ServicesChecked);
+                // one notification is enough for a run of failed writes.
+                let write_errors = self.audit.lock().unwrap().take_write_errors();
+                match write_errors.first() {
+                    Some(e) => Command::batch(vec![check, self.update(Message::SetNotification(
+                        e.to_string(), NotificationType::Sad))]),
+                    None => check,
+                }
+            }
+            Message::ServicesChecked(services) => {
+                self.status.services = Some(services);
+                Command::none()
+            }
+            Message::StateReceived(s) => {
+                self.status.state_received();
+                let mut command = Command::none();
+                if let Some(recorder) = self.recording.recorder.as_mut() {
+                    if let Err(e) = recorder.record(&s) {
+                        self.recording.recorder = None;
+                        command = self.update(Message::SetNotification(
+                            format!("Recording stopped: {}", e), NotificationType::Sad));
+                    }
+                }
+                // while replaying the recording decides what we show.
+                if self.replay.is_none() {
+                    let _ = self.update(Message::NewState(s));
+                }
+                command
+            }
+            Message::StateParseError(e, payload) => {
+                // every one goes in the log, a burst only pops up once.
+                let message = format!("Could not parse state message ({}): {}...", e, payload);
+                if self.status.parse_error() {
+                    self.update(Message::SetNotification(message, NotificationType::Sad))
+                } else {
+                    self.notifications.log(message, NotificationType::Sad);
+                    Command::none()
+                }
+            }
+            Message::RecordPathChanged(path) => {
+                self.recording.record_path = path;
+                Command::none()
+            }
+            Message::ToggleRecording => match self.recording.recorder.take() {
+                Some(r) => self.update(Message::SetNotification(
+                    format!("Recorded {} states to {}", r.count, r.path), NotificationType::Neutral)),
+                None => match Recorder::create(&self.recording.record_path) {
+                    Ok(r) => {
+                        self.recording.recorder = Some(r);
+                        Command::none()
+                    },
+                    Err(e) => self.update(Message::SetNotification(
+                        format!("Could not start recording: {}", e), NotificationType::Sad)),
+                },
+            },
+            Message::ReplayPathChanged(path) => {
+                self.recording.replay_path = path;
+                Command::none()
+            }
+            Message::StartReplay => match Replay::load(&self.recording.replay_path) {
+                Ok(replay) => {
+                    let first = replay.current();
+                    self.replay = Some(replay);
+                    match first {
+                        Some(s) => self.update(Message::NewState(s)),
+                        None => Command::none(),
+                    }
+                },
+                Err(e) => self.update(Message::SetNotification(
+                    format!("Could not load replay: {}", e),
+                    NotificationType::Sad)),
+            },
+            Message::StopReplay => {
+                self.replay = None;
+                Command::none()
+            }
+            Message::ReplayTick => match self.replay.as_mut().and_then(|r| r.tick()) {
+                Some(s) => self.update(Message::NewState(s)),
+                None => Command::none(),
+            },
+            Message::ReplayPlayPause => match self.replay.as_mut().and_then(|r| r.play_pause()) {
+                Some(s) => self.update(Message::NewState(s)),
+                None => Command::none(),
+            },
+            Message::ReplaySpeed(speed) => {
+                if let Some(replay) = self.replay.as_mut() {
+                    replay.set_speed(speed);
+                }
                 Command::none()
             }
+            Message::ReplaySeek(position) => match self.replay.as_mut().and_then(|r| r.seek(position)) {
+                Some(s) => self.update(Message::NewState(s)),
+                None => Command::none(),
+            },
             Message::NewState(s) => {
                 if let SPOpViewerState::Loaded {
                     model_info,
@@ -353,29 +676,26 @@ impl Application for SPOpViewer {
                     footer: _,
                 } = &mut self.ui_state
                 {
-                    // self.state = s;
-                    let mut new_state = vec![];
-                    for (k,v) in s.projection().state {
-                        match model_info.state.iter_mut().find(|si| &si.path == k) {
-                            Some(ref mut si) => si.value = v.value().clone(),
-                            None => new_state.push(StateInfo {
-                                path: k.clone(),
-                                value: v.value().clone(),
-                                new_value: String::new(),
-                                new_value_state: text_input::State::new(),
-                            }),
-                        }
-                    }
-                    model_info.state.extend(new_state);
+                    model_info.state.update_from(&s);
                 }
                 Command::none()
             }
             Message::UpdateModel => match self.ui_state {
                 SPOpViewerState::Loading => Command::none(),
                 _ => {
-                    self.ui_state = SPOpViewerState::Loading;
+                    let old = std::mem::replace(&mut self.ui_state, SPOpViewerState::Loading);
+                    if let SPOpViewerState::Loaded { model_info, current_view, scroll, footer } = old {
+                        self.kept = Some(KeptView {
+                            model_info,
+                            current_view,
+                            scroll,
+                            footer,
+                            intention_editor: self.intention_editor.take(),
+                            what_if: self.what_if.take(),
+                        });
+                    }
                     Command::perform(
-                        get_model(self.get_model_client.clone()),
+                        get_model(self.backend.clone()),
                         Message::ModelUpdate,
                     )
                 }
@@ -384,9 +704,12 @@ impl Application for SPOpViewer {
                 let new_state = SPState::new_from_values(&[(path.clone(), change_to.clone())]);
                 let new_state = SPStateJson::from_state_flat(&new_state);
                 let json = new_state.to_json().to_string();
-                Command::perform(set_state(self.set_state_client.clone(), json), move |_| {
-                    Message::SetNotification(format!("{} set to {}!", path, change_to),
-                                             NotificationType::Happy)
+                let cause = format!("ResetOperation({}, {})", path, change_to);
+                Command::perform(set_state(self.backend.clone(), self.audit.clone(), cause, json),
+                                 move |result| match result {
+                    Ok(()) => Message::SetNotification(format!("{} set to {}!", path, change_to),
+                                                       NotificationType::Happy),
+                    Err(e) => failed_state_change(&[path], e),
                 })
             },
             Message::SetEstimatedCylinders => {
@@ -397,21 +720,18 @@ impl Application for SPOpViewer {
                     footer: _,
                 } = &mut self.ui_state
                 {
-                    let updated_state: Vec<_> = model_info.estimated_locations
-                        .iter()
-                        .enumerate()
-                        .map(|(i, bi)| {
-                            let prefix = "lab_scenario_1/product_state";
-                            let path = SPPath::from_string(&format!("{}/buffer_{}",prefix,i+1));
-                            (path, bi.value.to_spvalue())
-                        }).collect();
+                    let updated_state = model_info.goal_values(BufferLocationType::Estimated);
 
                     let new_state = SPState::new_from_values(&updated_state);
                     let new_state = SPStateJson::from_state_flat(&new_state);
                     let json = new_state.to_json().to_string();
-                    Command::perform(set_state(self.set_state_client.clone(), json), |_| {
-                        Message::SetNotification("Updated the state".into(),
-                                                 NotificationType::Neutral)
+                    let paths: Vec<_> = updated_state.into_iter().map(|(p, _)| p).collect();
+                    let cause = "SetEstimatedCylinders".to_string();
+                    Command::perform(set_state(self.backend.clone(), self.audit.clone(), cause, json),
+                                     move |result| match result {
+                        Ok(()) => Message::SetNotification("Updated the state".into(),
+                                                           NotificationType::Neutral),
+                        Err(e) => failed_state_change(&paths, e),
                     })
                 } else {
                     Command::none()
@@ -425,35 +745,133 @@ impl Application for SPOpViewer {
                     footer: _,
                 } = &mut self.ui_state
                 {
-                    let predicate: Vec<_> = model_info.buffers_locations
-                        .iter()
-                        .enumerate()
-                        .map(|(i, bi)| {
-                            let prefix = "lab_scenario_1/product_state";
-                            let path = SPPath::from_string(&format!("{}/buffer_{}",prefix,i+1));
-
+                    let predicate: Vec<_> = model_info.goal_values(BufferLocationType::Goal)
+                        .into_iter()
+                        .map(|(path, value)| {
                             Predicate::EQ(PredicateValue::SPPath(path, None),
-                                          PredicateValue::SPValue(bi.value.to_spvalue()))
+                                          PredicateValue::SPValue(value))
                         }).collect();
                     let post = Predicate::AND(predicate);
-                    let mut model = Model::new("lab_scenario_1");
+                    let mut model = Model::new(&model_info.goal_layout.model_name);
                         model.add_intention("test_intention", false,
                                             &Predicate::TRUE,
                                             &post,
                                             &[]);
-                    Command::perform(set_model(self.set_model_client.clone(), model), move |_| {
-                        Message::SetNotification(//"Updated the state".into(),
-                            format!("new intention: {}", post),
-                            NotificationType::Neutral)
+                    self.check_and_send(PendingIntention {
+                        model,
+                        post,
+                        cause: "SendGoalCylinders".to_string(),
                     })
                 } else {
                     Command::none()
                 }
             }
+            Message::IntentionEdit(IntentionEdit::Send) => match &self.intention_editor {
+                Some(editor) => match editor.build() {
+                    Ok((pre, post)) => {
+                        let mut model = Model::new(&editor.model_name);
+                        model.add_intention(editor.name.trim(), editor.auto_run, &pre, &post, &[]);
+                        let cause = format!("IntentionEdit(Send {})", editor.name.trim());
+                        self.check_and_send(PendingIntention { model, post, cause })
+                    },
+                    Err(e) => self.update(Message::SetNotification(
+                        format!("Could not send intention: {}", e), NotificationType::Sad)),
+                },
+                None => Command::none(),
+            },
+            Message::IntentionEdit(IntentionEdit::Check) => {
+                let plan = self.intention_editor.as_ref()
+                    .and_then(|e| e.build().ok())
+                    .and_then(|(_, post)| self.plan_to(None, post));
+                match plan {
+                    Some(plan) => Command::perform(plan, Message::IntentionChecked),
+                    None => Command::none(),
+                }
+            },
+            Message::IntentionChecked(result) => {
+                if let Some(editor) = self.intention_editor.as_mut() {
+                    editor.check = Some(match result {
+                        Ok(plan) => plan.summary(),
+                        Err(e) => e.to_string(),
+                    });
+                }
+                Command::none()
+            },
+            Message::GoalChecked(pending, result) => match result {
+                Ok(plan) if plan.found => {
+                    let notify = self.update(Message::SetNotification(
+                        format!("Goal {}, sending", plan.summary()), NotificationType::Neutral));
+                    Command::batch(vec![notify, self.send_intention(pending.model, pending.post, pending.cause)])
+                },
+                // a bounded search or a failing planner proves nothing, let the user decide.
+                Ok(plan) => {
+                    let reason = format!("The planner found {}. A longer plan may still exist.", plan.summary());
+                    self.ask_send_anyway(pending, reason)
+                },
+                Err(e) => self.ask_send_anyway(pending, format!("Could not check the goal: {}", e)),
+            },
+            Message::WhatIf(WhatIfEdit::CopyState) => {
+                if let (Some(what_if), SPOpViewerState::Loaded { model_info, .. }) = (self.what_if.as_mut(), &self.ui_state) {
+                    what_if.copy_state(&model_info.get_spstate());
+                }
+                Command::none()
+            },
+            Message::WhatIf(WhatIfEdit::Plan) => {
+                let live = match &self.ui_state {
+                    SPOpViewerState::Loaded { model_info, .. } => model_info.get_spstate(),
+                    _ => return Command::none(),
+                };
+                let what_if = match self.what_if.as_ref() {
+                    Some(what_if) => what_if,
+                    None => return Command::none(),
+                };
+                let start_and_goal = what_if.start_state(live).and_then(|s| Ok((s, what_if.goal()?)));
+                match start_and_goal {
+                    Ok((start, goal)) => match self.plan_to(Some(start.clone()), goal) {
+                        Some(plan) => {
+                            if let Some(what_if) = self.what_if.as_mut() {
+                                what_if.planning = true;
+                            }
+                            Command::perform(plan, move |result| Message::WhatIfPlanned(start, result))
+                        },
+                        None => Command::none(),
+                    },
+                    Err(e) => self.update(Message::SetNotification(
+                        format!("Cannot plan: {}", e), NotificationType::Sad)),
+                }
+            },
+            Message::WhatIf(edit) => {
+                if let Some(what_if) = self.what_if.as_mut() {
+                    what_if.update(edit);
+                }
+                Command::none()
+            },
+            Message::WhatIfPlanned(start, result) => {
+                if let Some(what_if) = self.what_if.as_mut() {
+                    what_if.planning = false;
+                    what_if.result = Some((result.map_err(|e| e.to_string()), start));
+                }
+                Command::none()
+            },
+            Message::IntentionEdit(edit) => {
+                if let Some(editor) = self.intention_editor.as_mut() {
+                    editor.update(edit);
+                }
+                Command::none()
+            }
         }
     }
 
     fn view(&mut self) -> Element<Message> {
+        if let Some(confirmation) = self.confirmation.as_mut() {
+            return Container::new(confirmation.view())
+                .width(Length::Fill)
+                .height(Length::Fill)
+                .center_x()
+                .center_y()
+                .into();
+        }
+
         let content = match &mut self.ui_state {
             SPOpViewerState::Loading => Column::new()
                 .width(Length::Shrink)
@@ -464,11 +882,9 @@ impl Application for SPOpViewer {
                 scroll,
                 footer,
             } => {
-                let height = if self.notification.is_some() {
-                    500
-                } else {
-                    600
-                };
+                let height = 600 - 100 * self.notifications.visible() as u16;
+                let height = if self.replay.is_some() { height - 50 } else { height };
+                let height = height - 40; // status bar
                 let contents = Column::new()
                     .spacing(20)
                     .padding(10)
@@ -478,31 +894,61 @@ impl Application for SPOpViewer {
                                   &mut self.filter_edit_state,
                                   "Filter...",
                                   &self.filter_string,
-                                  |s| Message::FilterChanged(s))))
+                                  |s| Message::FilterChanged(s))));
+                let contents = match self.replay.as_mut() {
+                    Some(replay) => contents.push(replay.view()),
+                    None => contents,
+                };
+                let contents = contents
                     .push(Row::new()
                           .height(Length::Units(height))
                           .push(match current_view {
-                              View::StateView => model_info.view_state(&self.filter_string, scroll),
-                              View::OperationView => model_info.view_ops(),
-                              View::IntentionView => model_info.view_ints(),
-                              View::TPlanView => model_info.view_tplan(),
-                              View::OPlanView => model_info.view_oplan(),
-                              View::DemoGoalView => model_info.view_demo_goal(),
+                              View::StateView => model_info.view_state(&self.filter_string, scroll,
+                                                                       self.read_only),
+                              View::OperationView => model_info.view_ops(&self.filter_string, self.read_only),
+                              View::IntentionView => model_info.view_ints(&self.filter_string, self.read_only),
+                              View::TPlanView => model_info.view_tplan(&self.filter_string),
+                              View::OPlanView => model_info.view_oplan(&self.filter_string),
+                              View::DemoGoalView => model_info.view_demo_goal(self.read_only),
+                              View::IntentionEditorView => match self.intention_editor.as_mut() {
+                                  Some(editor) => editor.view(self.read_only),
+                                  None => Text::new("No model").into(),
+                              },
+                              View::ModelView => model_info.view_model(&self.filter_string),
+                              View::ModelDiffView => match self.model_diff.as_mut() {
+                                  Some(diff) => diff.view(&self.filter_string),
+                                  None => Text::new("Nothing to compare with until the model is reloaded").into(),
+                              },
+                              View::WhatIfView => match self.what_if.as_mut() {
+                                  Some(what_if) => what_if.view(&self.filter_string),
+                                  None => Text::new("No model").into(),
+                              },
+                              View::RecordingView => self.recording.view(),
+                              View::NotificationView => self.notifications.view_history(),
+                              View::AuditView => self.audit_view.view(&self.audit.lock().unwrap()),
                           }))
-                    .push(footer.view());
-                if let Some(n) = self.notification.as_mut() {
-                    contents.push(n.view())
-                } else {
-                    contents
-                }
+                    .push(footer.view(self.read_only))
+                    .push(self.status.view());
+                contents.push(self.notifications.view_active())
             },
             SPOpViewerState::Errored {
-                get_model_button, ..
-            } => Column::new()
-                .spacing(20)
-                .align_items(Alignment::End)
-                .push(Text::new("Could not get model...").size(40))
-                .push(button(get_model_button, "Try again").on_press(Message::UpdateModel)),
+                get_model_button, error,
+            } => {
+                let retry = match self.retry_at {
+                    Some(t) => format!("Retrying in {} s (attempt {})",
+                                       t.saturating_duration_since(Instant::now()).as_secs(),
+                                       self.model_retries + 1),
+                    None => String::new(),
+                };
+                Column::new()
+                    .spacing(20)
+                    .align_items(Alignment::End)
+                    .push(Text::new("Could not get model...").size(40))
+                    .push(Text::new(error.to_string()).size(16).color([0.8, 0.1, 0.1]))
+                    .push(Text::new(retry).size(16))
+                    .push(button(get_model_button, "Try again").on_press(Message::UpdateModel))
+                    .push(self.status.view())
+            },
         };
 
         Container::new(content)
@@ -514,26 +960,143 @@ impl Application for SPOpViewer {
     }
 }
 
-#[derive(Debug, Clone)]
-pub enum Error {
-    RosError,
-    SerdeError,
-}
+impl SPOpViewer {
+    /// Everything but picking the backend, so tests can bring their own.
+    fn with_backend(backend: Arc<dyn Backend>, config: &Config) -> (SPOpViewer, Command<Message>) {
+        let receiver = backend.take_state_receiver();
+        let mut status = ConnectionStatus::new(Duration::from_millis(config.stale_after_ms));
+        if let Some(r) = &receiver {
+            status.channel(r.mode(), r.dropped_counter());
+        }
+        let (audit, audit_error) = AuditLog::create(&config.audit_dir);
+        let mut commands = vec![Command::perform(get_model(backend.clone()), Message::ModelUpdate)];
+        if let Some(e) = audit_error {
+            commands.push(Command::perform(async {}, move |_| Message::SetNotification(
+                format!("Commands will not be saved: {}", e), NotificationType::Sad)));
+        }
 
-impl From<r2r::Error> for Error {
-    fn from(error: r2r::Error) -> Error {
-        dbg!(error);
+        (
+            SPOpViewer {
+                backend: backend.clone(),
+                new_state_receiver: Mutex::new(receiver),
+                ui_state: SPOpViewerState::Loading,
+                status,
+                model_retries: 0,
+                retry_at: None,
+                notifications: Notifications::new(),
+                recording: Recording::new(),
+                replay: None,
+                filter_string: String::new(),
+                filter_edit_state: text_input::State::new(),
+                audit: Arc::new(Mutex::new(audit)),
+                audit_view: AuditView::default(),
+                confirmation: None,
+                read_only: config.read_only,
+                goal_layout: GoalLayoutSource::from_config(config),
+                intention_editor: None,
+                what_if: None,
+                kept: None,
+                model_diff: None,
+                plan_max_steps: config.plan_max_steps,
+            },
+            Command::batch(commands),
+        )
+    }
 
-        Error::RosError
+    /// Plan to `goal` in the loaded model, from `state` or the current state.
+    fn plan_to(&self, state: Option<SPState>, goal: Predicate)
+               -> Option<impl std::future::Future<Output = Result<Plan, Error>>> {
+        match &self.ui_state {
+            SPOpViewerState::Loaded { model_info, .. } => {
+                let state = state.unwrap_or_else(|| model_info.get_spstate());
+                Some(planning::plan(model_info.compiled_model.clone(), state, goal, self.plan_max_steps))
+            },
+            _ => None,
+        }
     }
-}
 
-impl From<serde_json::Error> for Error {
-    fn from(error: serde_json::Error) -> Error {
-        dbg!(error);
+    fn check_and_send(&self, pending: PendingIntention) -> Command<Message> {
+        match self.plan_to(None, pending.post.clone()) {
+            Some(plan) => Command::perform(plan, move |result| Message::GoalChecked(pending, result)),
+            None => Command::none(),
+        }
+    }
 
-        Error::SerdeError
+    /// Does not replace a confirmation that is already open, that one would
+    /// be silently lost.
+    fn ask_send_anyway(&mut self, pending: PendingIntention, reason: String) -> Command<Message> {
+        if self.confirmation.is_some() {
+            return self.update(Message::SetNotification(
+                format!("Intention not sent: {} (another confirmation is open)", reason),
+                NotificationType::Sad));
+        }
+        let notify = self.update(Message::SetNotification(
+            format!("Intention not sent: {}", reason), NotificationType::Sad));
+        self.confirmation = Some(Confirmation::new(Pending::Intention { pending, reason }));
+        notify
+    }
+
+    fn send_intention(&self, model: Model, post: Predicate, cause: String) -> Command<Message> {
+        Command::perform(set_model(self.backend.clone(), self.audit.clone(), cause, model),
+                         move |result| {
+            match result {
+                Ok(()) => Message::SetNotification(//"Updated the state".into(),
+                    format!("new intention: {}", post),
+                    NotificationType::Neutral),
+                Err(e) => Message::SetNotification(
+                    format!("Could not send intention: {}", e),
+                    NotificationType::Sad),
+            }
+        })
     }
+
+    fn commit_state_edits(&mut self, only: Option<&SPPath>, cause: String) -> Command<Message> {
+        if let SPOpViewerState::Loaded { model_info, .. } = &mut self.ui_state {
+            let changes = model_info.take_state_edits(only);
+            if changes.is_empty() {
+                return Command::none();
+            }
+            let json = state_json(&changes);
+            let changed: Vec<_> = changes.iter().map(|(p, v)| format!("{} = {}", p, v)).collect();
+            let paths: Vec<_> = changes.into_iter().map(|(p, _)| p).collect();
+            Command::perform(set_state(self.backend.clone(), self.audit.clone(), cause, json),
+                             move |result| match result {
+                Ok(()) => Message::SetNotification(format!("set {}", changed.join(", ")),
+                                                   NotificationType::Happy),
+                Err(e) => failed_state_change(&paths, e),
+            })
+        } else {
+            Command::none()
+        }
+    }
+}
+
+impl Message {
+    /// Messages that end up in set_state or set_model.
+    fn writes(&self) -> bool {
+        matches!(self,
+                 Message::AskResetOperation(..)
+                 | Message::Confirm
+                 | Message::ResetOperation(..)
+                 | Message::StateValueCommit(..)
+                 | Message::StateValueSet(..)
+                 | Message::ApplyStateEdits
+                 | Message::SetEstimatedCylinders
+                 | Message::SendGoalCylinders
+                 | Message::IntentionEdit(IntentionEdit::Send))
+    }
+}
+
+fn failed_state_change(paths: &[SPPath], error: Error) -> Message {
+    let paths: Vec<_> = paths.iter().map(|p| p.to_string()).collect();
+    Message::SetNotification(format!("Could not set {}: {}", paths.join(", "), error),
+                             NotificationType::Sad)
+}
+
+fn state_json(values: &[(SPPath, SPValue)]) -> String {
+    let new_state = SPState::new_from_values(values);
+    let new_state = SPStateJson::from_state_flat(&new_state);
+    new_state.to_json().to_string()
 }
 
 fn button<'a>(state: &'a mut button::State, text: &str) -> Button<'a, Message> {
@@ -563,3 +1126,90 @@ pub(crate) mod style {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_models;
+
+    /// Run the futures of a command and collect what they produce.
+    async fn run(command: Command<Message>) -> Vec<Message> {
+        let mut messages = vec![];
+        for action in command.actions() {
+            if let iced_native::command::Action::Future(f) = action {
+                messages.push(f.await);
+            }
+        }
+        messages
+    }
+
+    fn value(viewer: &SPOpViewer, path: &SPPath) -> Option<SPValue> {
+        match &viewer.ui_state {
+            SPOpViewerState::Loaded { model_info, .. } => model_info.state.value(path).cloned(),
+            _ => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn state_value_set_round_trips_through_the_mock() {
+        // our own directory, so runs do not leave audit files in the shared temp dir.
+        let audit_dir = std::env::temp_dir().join(format!("sp_op_viewer_round_trip_{}", std::process::id()));
+        let config = Config {
+            audit_dir: audit_dir.to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        let backend = Arc::new(MockBackend::new(test_models::compiled_model(), &config));
+        let (mut viewer, startup) = SPOpViewer::with_backend(backend, &config);
+        let mut receiver = viewer.new_state_receiver.lock().unwrap().take().expect("receiver");
+        for m in run(startup).await {
+            let _ = viewer.update(m);
+        }
+
+        let b = test_models::path("r/b");
+        let next_state = |update: Option<StateUpdate>| match update {
+            Some(StateUpdate::State(s)) => s,
+            other => panic!("expected a state, got {:?}", other),
+        };
+        let _ = viewer.update(Message::NewState(next_state(receiver.recv().await)));
+        assert_eq!(value(&viewer, &b), Some(SPValue::Bool(false)));
+
+        let messages = run(viewer.update(Message::StateValueSet(b.clone(), "true".into()))).await;
+        assert!(matches!(&messages[..], [Message::SetNotification(_, NotificationType::Happy)]));
+
+        // the mock echoes the change on the state stream.
+        let s = next_state(receiver.recv().await);
+        assert_eq!(s.sp_value_from_path(&b), Some(&SPValue::Bool(true)));
+        let _ = viewer.update(Message::NewState(s));
+        assert_eq!(value(&viewer, &b), Some(SPValue::Bool(true)));
+
+        let _ = std::fs::remove_dir_all(&audit_dir);
+    }
+
+    #[test]
+    fn an_open_confirmation_is_not_replaced() {
+        let audit_dir = std::env::temp_dir().join(format!("sp_op_viewer_confirmation_{}", std::process::id()));
+        let config = Config {
+            audit_dir: audit_dir.to_string_lossy().to_string(),
+            ..Config::default()
+        };
+        let backend = Arc::new(MockBackend::new(test_models::compiled_model(), &config));
+        let (mut viewer, _) = SPOpViewer::with_backend(backend, &config);
+        let b = test_models::path("r/b");
+        viewer.confirmation = Some(Confirmation::new(Pending::StateChange {
+            path: b.clone(),
+            current: Some(SPValue::Bool(false)),
+            new_value: SPValue::Bool(true),
+        }));
+
+        let pending = PendingIntention {
+            model: test_models::model(),
+            post: Predicate::TRUE,
+            cause: "test".into(),
+        };
+        let _ = viewer.ask_send_anyway(pending, "no plan found".into());
+        assert!(matches!(&viewer.confirmation,
+                         Some(Confirmation { pending: Pending::StateChange { path, .. }, .. }) if *path == b));
+
+        let _ = std::fs::remove_dir_all(&audit_dir);
+    }
+}
@@ -0,0 +1,163 @@
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use iced::{button, scrollable, Alignment, Button, Column, Element, Length, Row, Scrollable, Space, Text};
+use sp_domain::*;
+use std::collections::{HashMap, HashSet};
+use crate::components::{highlighted, with_hidden_count, StateInfo};
+use crate::state_store::StateStore;
+use crate::Message;
+
+/// Widget state for the model view.
+#[derive(Debug, Clone, Default)]
+pub struct ModelBrowser {
+    pub expanded: HashSet<SPPath>,
+    section_buttons: HashMap<SPPath, button::State>,
+    link_buttons: HashMap<SPPath, button::State>,
+    scroll: scrollable::State,
+}
+
+struct Section<'a> {
+    path: SPPath,
+    title: String,
+    variables: Vec<&'a Variable>,
+    transitions: Vec<&'a Transition>,
+    specs: Vec<&'a Spec>,
+}
+
+impl ModelBrowser {
+    pub fn toggle(&mut self, path: SPPath) {
+        if !self.expanded.remove(&path) {
+            self.expanded.insert(path);
+        }
+    }
+
+    pub fn view<'a>(&'a mut self, model: &Model, state: &StateStore<StateInfo>,
+                    filter: &str) -> Element<'a, Message> {
+        let matcher = SkimMatcherV2::default().ignore_case();
+        let matches = |p: &SPPath| matcher.fuzzy_indices(&p.to_string(), filter).map(|(_, i)| i);
+
+        let mut sections: Vec<Section> = model.resources
+            .iter()
+            .map(|r| Section {
+                path: r.path().clone(),
+                title: r.path().to_string(),
+                variables: r.variables.iter().collect(),
+                transitions: r.transitions.iter().collect(),
+                specs: r.specs.iter().collect(),
+            })
+            .collect();
+        sections.push(Section {
+            path: SPPath::from_slice(&["global"]),
+            title: "Global transitions and specifications".to_string(),
+            variables: vec![],
+            transitions: model.global_transitions.iter().collect(),
+            specs: model.global_specs.iter().collect(),
+        });
+
+        for s in &sections {
+            self.section_buttons.entry(s.path.clone()).or_default();
+            for v in &s.variables {
+                self.link_buttons.entry(v.path.clone()).or_default();
+            }
+        }
+        let mut section_buttons: HashMap<&SPPath, &mut button::State> = self.section_buttons.iter_mut().collect();
+        let mut link_buttons: HashMap<&SPPath, &mut button::State> = self.link_buttons.iter_mut().collect();
+        let expanded = &self.expanded;
+        let filtering = !filter.is_empty();
+        let mut hidden = 0;
+
+        let content = sections.into_iter().fold(Column::new().spacing(10), |col, s| {
+            let variables: Vec<_> = s.variables.iter().filter_map(|v| matches(&v.path).map(|i| (*v, i))).collect();
+            let transitions: Vec<_> = s.transitions.iter().filter_map(|t| matches(t.path()).map(|i| (*t, i))).collect();
+            let specs: Vec<_> = s.specs.iter().filter_map(|sp| matches(sp.path()).map(|i| (*sp, i))).collect();
+            let matching = variables.len() + transitions.len() + specs.len();
+            if filtering && matching == 0 && matches(&s.path).is_none() {
+                hidden += 1;
+                return col;
+            }
+
+            // while filtering everything with a match is open.
+            let is_expanded = expanded.contains(&s.path) || (filtering && matching > 0);
+            let marker = if is_expanded { "-" } else { "+" };
+            let label = format!("{} {} ({} variables, {} transitions, {} specs)", marker, s.title,
+                                s.variables.len(), s.transitions.len(), s.specs.len());
+            let header = Button::new(section_buttons.remove(&s.path).expect("button created above"),
+                                     Text::new(label).size(20))
+                .padding(4)
+                .on_press(Message::ToggleModelSection(s.path.clone()));
+            let col = col.push(header);
+            if !is_expanded {
+                return col;
+            }
+
+            let body = Column::new().spacing(5);
+            let body = if variables.is_empty() {
+                body
+            } else {
+                let body = body.push(Text::new("Variables").size(16));
+                variables.into_iter().fold(body, |body, (v, indices)| {
+                    let domain: Vec<_> = v.domain.iter().map(|d| d.to_string()).collect();
+                    let value = state.value(&v.path).map(|v| v.to_string()).unwrap_or("[no state]".into());
+                    let link = Button::new(link_buttons.remove(&v.path).expect("button created above"),
+                                           Text::new(value).size(14))
+                        .padding(2)
+                        .on_press(Message::ShowInState(v.path.clone()));
+                    body.push(Row::new()
+                              .spacing(10)
+                              .align_items(Alignment::Center)
+                              .push(Column::new().width(Length::FillPortion(3))
+                                    .push(highlighted(&v.path.to_string(), &indices, 14, [0.2, 0.2, 0.2])))
+                              .push(Text::new(format!("{:?}", v.value_type)).size(14).width(Length::FillPortion(1)))
+                              .push(Text::new(format!("[{}]", domain.join(", "))).size(14).width(Length::FillPortion(2)))
+                              .push(link))
+                })
+            };
+
+            // controlled, auto and effect transitions, each under its own heading.
+            let mut by_type: Vec<(String, Vec<(&Transition, Vec<usize>)>)> = vec![];
+            for (t, indices) in transitions {
+                let type_ = format!("{:?}", t.type_);
+                match by_type.iter_mut().find(|(k, _)| *k == type_) {
+                    Some((_, ts)) => ts.push((t, indices)),
+                    None => by_type.push((type_, vec![(t, indices)])),
+                }
+            }
+            let body = by_type.into_iter().fold(body, |body, (type_, ts)| {
+                let body = body.push(Text::new(format!("{} transitions", type_)).size(16));
+                ts.into_iter().fold(body, |body, (t, indices)| body.push(view_transition(t, &indices)))
+            });
+
+            let body = if specs.is_empty() {
+                body
+            } else {
+                let body = body.push(Text::new("Specifications").size(16));
+                specs.into_iter().fold(body, |body, (sp, indices)| {
+                    body.push(Column::new()
+                              .push(highlighted(&sp.path().to_string(), &indices, 14, [0.2, 0.2, 0.2]))
+                              .push(Text::new(format!("always: {}", sp.invariant)).size(12).color([0.4, 0.4, 0.4])))
+                })
+            };
+
+            col.push(Row::new()
+                     .push(Space::with_width(Length::Units(20)))
+                     .push(body))
+        });
+
+        Scrollable::new(&mut self.scroll)
+            .height(Length::Fill)
+            .push(with_hidden_count(content, hidden))
+            .into()
+    }
+}
+
+fn view_transition<'a>(t: &Transition, indices: &[usize]) -> Element<'a, Message> {
+    let detail = |label: &str, text: String| Text::new(format!("{}: {}", label, text)).size(12).color([0.4, 0.4, 0.4]);
+    let actions = |actions: &[Action]| actions.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ");
+    Column::new()
+        .push(highlighted(&t.path().to_string(), indices, 14, [0.2, 0.2, 0.2]))
+        .push(detail("guard", t.guard.to_string()))
+        .push(detail("actions", actions(&t.actions)))
+        .push(detail("runner guard", t.runner_guard.to_string()))
+        .push(detail("runner actions", actions(&t.runner_actions)))
+        .into()
+}
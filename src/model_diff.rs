@@ -0,0 +1,215 @@
+use chrono::{DateTime, Local};
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use iced::{scrollable, Column, Element, Length, Row, Scrollable, Text};
+use serde::Serialize;
+use sp_domain::*;
+use std::collections::BTreeMap;
+use crate::components::{model_transitions, model_variables, with_hidden_count};
+use crate::Message;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub category: &'static str,
+    pub path: String,
+    /// what changed, e.g. "guard: a -> b"
+    pub details: Vec<String>,
+}
+
+/// Structural differences between two versions of the model.
+#[derive(Debug, Clone)]
+pub struct ModelDiff {
+    pub time: DateTime<Local>,
+    pub changes: Vec<Change>,
+    scroll: scrollable::State,
+}
+
+impl ModelDiff {
+    pub fn new(old: &Model, new: &Model) -> Self {
+        let mut changes = vec![];
+        let key = |p: &SPPath| p.to_string();
+
+        diff_items(&mut changes, "variable",
+                   model_variables(old).map(|v| (key(&v.path), v)),
+                   model_variables(new).map(|v| (key(&v.path), v)),
+                   |a, b| {
+                       let mut d = vec![];
+                       if a.value_type != b.value_type {
+                           d.push(format!("type: {:?} -> {:?}", a.value_type, b.value_type));
+                       }
+                       if a.domain != b.domain {
+                           d.push(format!("domain: {} -> {}", values(&a.domain), values(&b.domain)));
+                       }
+                       d
+                   });
+        let (old_ts, new_ts) = (model_transitions(old), model_transitions(new));
+        diff_items(&mut changes, "transition",
+                   old_ts.iter().map(|t| (key(t.path()), t)),
+                   new_ts.iter().map(|t| (key(t.path()), t)),
+                   |a, b| transition_details("", a, b));
+        diff_items(&mut changes, "operation",
+                   old.operations.iter().map(|o| (key(o.path()), o)),
+                   new.operations.iter().map(|o| (key(o.path()), o)),
+                   |a, b| {
+                       let mut d = transition_details("start ", &a.start, &b.start);
+                       d.extend(transition_details("finish ", &a.finish, &b.finish));
+                       d
+                   });
+        diff_items(&mut changes, "intention",
+                   old.intentions.iter().map(|i| (key(i.path()), i)),
+                   new.intentions.iter().map(|i| (key(i.path()), i)),
+                   |a, b| {
+                       let mut d = transition_details("start ", &a.start, &b.start);
+                       d.extend(transition_details("finish ", &a.finish, &b.finish));
+                       if a.auto_run != b.auto_run {
+                           d.push(format!("auto run: {} -> {}", a.auto_run, b.auto_run));
+                       }
+                       d
+                   });
+
+        ModelDiff {
+            time: Local::now(),
+            changes,
+            scroll: scrollable::State::new(),
+        }
+    }
+
+    pub fn view(&mut self, filter: &str) -> Element<Message> {
+        let matcher = SkimMatcherV2::default().ignore_case();
+        let mut hidden = 0;
+        let rows = self.changes
+            .iter()
+            .fold(Column::new().spacing(8), |col, c| {
+                if matcher.fuzzy_match(&c.path, filter).is_none() {
+                    hidden += 1;
+                    return col;
+                }
+                let (sign, color) = match c.kind {
+                    ChangeKind::Added => ("+", [0.0, 0.5, 0.0]),
+                    ChangeKind::Removed => ("-", [0.8, 0.1, 0.1]),
+                    ChangeKind::Changed => ("~", [0.0, 0.0, 0.6]),
+                };
+                let col = col.push(Row::new()
+                                   .spacing(10)
+                                   .push(Text::new(sign).size(16).color(color))
+                                   .push(Text::new(c.category).size(16).color([0.5, 0.5, 0.5]))
+                                   .push(Text::new(&c.path).size(16).color(color)));
+                c.details.iter().fold(col, |col, d| {
+                    col.push(Text::new(format!("    {}", d)).size(12).color([0.3, 0.3, 0.3]))
+                })
+            });
+
+        let title = if self.changes.is_empty() {
+            format!("No changes in the model reloaded at {}", self.time.format("%H:%M:%S"))
+        } else {
+            format!("{} changes in the model reloaded at {}", self.changes.len(), self.time.format("%H:%M:%S"))
+        };
+        Column::new()
+            .spacing(10)
+            .push(Text::new(title).size(20))
+            .push(Scrollable::new(&mut self.scroll)
+                  .height(Length::Fill)
+                  .push(with_hidden_count(rows, hidden)))
+            .into()
+    }
+}
+
+/// Added and removed by path. Items on both sides are changed if they
+/// serialize differently, `details` says how.
+fn diff_items<'a, T: Serialize + 'a>(changes: &mut Vec<Change>, category: &'static str,
+                                    old: impl Iterator<Item = (String, &'a T)>,
+                                    new: impl Iterator<Item = (String, &'a T)>,
+                                    details: impl Fn(&T, &T) -> Vec<String>) {
+    let old: BTreeMap<String, &T> = old.collect();
+    let new: BTreeMap<String, &T> = new.collect();
+    for (path, o) in &old {
+        match new.get(path) {
+            None => changes.push(Change { kind: ChangeKind::Removed, category, path: path.clone(), details: vec![] }),
+            Some(n) if serde_json::to_value(o).ok() != serde_json::to_value(n).ok() => {
+                changes.push(Change { kind: ChangeKind::Changed, category, path: path.clone(), details: details(o, n) })
+            },
+            Some(_) => (),
+        }
+    }
+    for path in new.keys().filter(|p| !old.contains_key(*p)) {
+        changes.push(Change { kind: ChangeKind::Added, category, path: path.clone(), details: vec![] });
+    }
+}
+
+fn transition_details(prefix: &str, a: &Transition, b: &Transition) -> Vec<String> {
+    let actions = |actions: &[Action]| actions.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ");
+    let mut d = vec![];
+    let mut compare = |what: &str, a: String, b: String| {
+        if a != b {
+            d.push(format!("{}{}: {} -> {}", prefix, what, a, b));
+        }
+    };
+    compare("guard", a.guard.to_string(), b.guard.to_string());
+    compare("runner guard", a.runner_guard.to_string(), b.runner_guard.to_string());
+    compare("actions", actions(&a.actions), actions(&b.actions));
+    compare("runner actions", actions(&a.runner_actions), actions(&b.runner_actions));
+    compare("type", format!("{:?}", a.type_), format!("{:?}", b.type_));
+    d
+}
+
+fn values(values: &[SPValue]) -> String {
+    format!("[{}]", values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_models;
+
+    fn summary(diff: &ModelDiff) -> Vec<String> {
+        diff.changes.iter().map(|c| format!("{:?} {} {}", c.kind, c.category, c.path)).collect()
+    }
+
+    #[test]
+    fn same_model_has_no_changes() {
+        let model = test_models::model();
+        assert!(ModelDiff::new(&model, &model).changes.is_empty());
+    }
+
+    #[test]
+    fn added_removed_and_changed_variables() {
+        let old = test_models::model();
+        let mut new = test_models::model();
+        new.resources[0].variables.retain(|v| v.path != test_models::path("r/b"));
+        new.resources[0].variables[0].domain.push(SPValue::Int32(3));
+        new.resources[0].variables.push(test_models::variable("r/c", SPValueType::Bool, vec![]));
+
+        let diff = ModelDiff::new(&old, &new);
+        assert_eq!(summary(&diff), vec![
+            "Removed variable r/b",
+            "Changed variable r/pos",
+            "Added variable r/c",
+        ]);
+        assert_eq!(diff.changes[1].details, vec!["domain: [0, 1, 2] -> [0, 1, 2, 3]"]);
+    }
+
+    #[test]
+    fn changed_intentions_say_what_changed() {
+        let goal = |value: bool| Predicate::EQ(PredicateValue::SPPath(test_models::path("r/b"), None),
+                                               PredicateValue::SPValue(value.to_spvalue()));
+        let mut old = test_models::model();
+        old.add_intention("to_true", false, &Predicate::TRUE, &goal(true), &[]);
+        let mut new = test_models::model();
+        new.add_intention("to_true", true, &Predicate::TRUE, &goal(false), &[]);
+
+        let diff = ModelDiff::new(&old, &new);
+        assert_eq!(diff.changes.len(), 1);
+        let change = &diff.changes[0];
+        assert_eq!((change.kind, change.category), (ChangeKind::Changed, "intention"));
+        assert!(change.details.iter().any(|d| d.contains("guard") && d.contains("false")));
+        assert!(change.details.contains(&"auto run: false -> true".to_string()));
+    }
+}
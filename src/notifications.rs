@@ -0,0 +1,176 @@
+use chrono::{DateTime, Local};
+use iced::{button, scrollable, text_input, Alignment, Button, Checkbox, Column, Element, Length,
+           Row, Scrollable, Text, TextInput};
+use std::io::Write;
+use std::time::Duration;
+use crate::components::{Notification, NotificationType};
+use crate::{Error, Message};
+
+// only the newest few are stacked on screen, the rest wait for their timeout.
+pub const MAX_VISIBLE: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub time: DateTime<Local>,
+    pub message: String,
+    pub type_: NotificationType,
+}
+
+impl HistoryEntry {
+    pub fn to_line(&self) -> String {
+        format!("{} {:?}: {}", self.time.format("%Y-%m-%d %H:%M:%S"), self.type_, self.message)
+    }
+}
+
+/// The notifications currently on screen and everything shown this session.
+pub struct Notifications {
+    next_id: u64,
+    active: Vec<Notification>,
+    history: Vec<HistoryEntry>,
+
+    show_happy: bool,
+    show_neutral: bool,
+    show_sad: bool,
+    pub export_path: String,
+    export_path_state: text_input::State,
+    export_button: button::State,
+    scroll: scrollable::State,
+}
+
+impl Notifications {
+    pub fn new() -> Self {
+        Notifications {
+            next_id: 0,
+            active: vec![],
+            history: vec![],
+            show_happy: true,
+            show_neutral: true,
+            show_sad: true,
+            export_path: format!("sp_notifications_{}.txt", Local::now().format("%Y%m%d_%H%M%S")),
+            export_path_state: text_input::State::new(),
+            export_button: button::State::new(),
+            scroll: scrollable::State::new(),
+        }
+    }
+
+    /// Only put it in the history, without showing it.
+    pub fn log(&mut self, message: String, type_: NotificationType) {
+        self.history.push(HistoryEntry {
+            time: Local::now(),
+            message,
+            type_,
+        });
+    }
+
+    /// Queue a notification. Returns its id and how long it should stay up.
+    pub fn push(&mut self, message: String, type_: NotificationType) -> (u64, Duration) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.log(message.clone(), type_);
+        self.active.push(Notification::new(id, message, type_));
+
+        // give bad news some more time.
+        let timeout = match type_ {
+            NotificationType::Sad => Duration::from_millis(6000),
+            _ => Duration::from_millis(2500),
+        };
+        (id, timeout)
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.active.retain(|n| n.id != id);
+    }
+
+    pub fn visible(&self) -> usize {
+        self.active.len().min(MAX_VISIBLE)
+    }
+
+    pub fn set_filter(&mut self, type_: NotificationType, show: bool) {
+        match type_ {
+            NotificationType::Happy => self.show_happy = show,
+            NotificationType::Neutral => self.show_neutral = show,
+            NotificationType::Sad => self.show_sad = show,
+        }
+    }
+
+    fn shown(&self, type_: NotificationType) -> bool {
+        match type_ {
+            NotificationType::Happy => self.show_happy,
+            NotificationType::Neutral => self.show_neutral,
+            NotificationType::Sad => self.show_sad,
+        }
+    }
+
+    /// Write the (filtered) history to a text file.
+    pub fn export(&self) -> Result<usize, Error> {
+        let what = format!("could not write {}", self.export_path);
+        let mut file = std::fs::File::create(&self.export_path).map_err(|e| Error::io(&what, e))?;
+        let mut count = 0;
+        for entry in self.history.iter().filter(|e| self.shown(e.type_)) {
+            writeln!(file, "{}", entry.to_line()).map_err(|e| Error::io(&what, e))?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// The newest notifications, stacked.
+    pub fn view_active(&mut self) -> Column<Message> {
+        let skip = self.active.len() - self.visible();
+        self.active
+            .iter_mut()
+            .skip(skip)
+            .fold(Column::new().spacing(5), |col, n| col.push(n.view()))
+    }
+
+    pub fn view_history(&mut self) -> Element<Message> {
+        let filters = Row::new()
+            .spacing(20)
+            .push(Checkbox::new(self.show_happy, "Happy",
+                                |b| Message::NotificationFilter(NotificationType::Happy, b)))
+            .push(Checkbox::new(self.show_neutral, "Neutral",
+                                |b| Message::NotificationFilter(NotificationType::Neutral, b)))
+            .push(Checkbox::new(self.show_sad, "Sad",
+                                |b| Message::NotificationFilter(NotificationType::Sad, b)));
+
+        let export = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(TextInput::new(&mut self.export_path_state, "notifications.txt",
+                                 &self.export_path, Message::NotificationExportPathChanged)
+                  .padding(5))
+            .push(Button::new(&mut self.export_button, Text::new("Export"))
+                  .padding(8)
+                  .style(crate::style::Button::Primary)
+                  .on_press(Message::ExportNotifications));
+
+        let (show_happy, show_neutral, show_sad) = (self.show_happy, self.show_neutral, self.show_sad);
+        let entries = self.history
+            .iter()
+            .rev()
+            .filter(|e| match e.type_ {
+                NotificationType::Happy => show_happy,
+                NotificationType::Neutral => show_neutral,
+                NotificationType::Sad => show_sad,
+            })
+            .fold(Column::new().spacing(5), |col, e| {
+                let color = match e.type_ {
+                    NotificationType::Happy => [0.0, 0.5, 0.0],
+                    NotificationType::Neutral => [0.2, 0.2, 0.2],
+                    NotificationType::Sad => [0.8, 0.1, 0.1],
+                };
+                col.push(Row::new()
+                         .spacing(10)
+                         .push(Text::new(e.time.format("%H:%M:%S").to_string()).size(14).color([0.5, 0.5, 0.5]))
+                         .push(Text::new(&e.message).size(14).color(color)))
+            });
+
+        Column::new()
+            .spacing(10)
+            .push(filters)
+            .push(export)
+            .push(Scrollable::new(&mut self.scroll)
+                  .height(Length::Fill)
+                  .push(entries))
+            .into()
+    }
+}
@@ -0,0 +1,96 @@
+use sp_domain::*;
+use sp_formal::{CompiledModel, TransitionSystemModel};
+use std::time::Duration;
+use crate::Error;
+
+/// One step of a locally computed plan.
+#[derive(Debug, Clone)]
+pub struct PlanStep {
+    pub transition: SPPath,
+    /// the state after taking the transition
+    pub state: SPState,
+}
+
+/// What the planner found.
+#[derive(Debug, Clone)]
+pub struct Plan {
+    pub found: bool,
+    pub length: u32,
+    pub steps: Vec<PlanStep>,
+    pub time: Duration,
+    pub max_steps: u32,
+}
+
+impl Plan {
+    pub fn summary(&self) -> String {
+        if self.found {
+            format!("reachable in {} steps ({} ms)", self.length, self.time.as_millis())
+        } else {
+            format!("no plan within {} steps", self.max_steps)
+        }
+    }
+
+    /// The state each step is taken from, `from` for the first step and
+    /// the state the previous step left for the rest.
+    pub fn states_before<'a>(&'a self, from: &'a SPState) -> Vec<&'a SPState> {
+        std::iter::once(from)
+            .chain(self.steps.iter().map(|s| &s.state))
+            .take(self.steps.len())
+            .collect()
+    }
+}
+
+/// Plan from `state` to `goal` with the planner in sp_formal. The
+/// planner runs an external model checker, so keep it off the ui thread.
+pub async fn plan(compiled_model: CompiledModel, state: SPState, goal: Predicate,
+                  max_steps: u32) -> Result<Plan, Error> {
+    tokio::task::spawn_blocking(move || {
+        let ts_model = TransitionSystemModel::from(&compiled_model.model);
+        let result = sp_formal::planning::plan(&ts_model, &[(goal, None)], &state, max_steps);
+        if !result.plan_found && !result.raw_error_output.is_empty() {
+            return Err(Error::Planner { message: result.raw_error_output });
+        }
+        Ok(Plan {
+            found: result.plan_found,
+            length: result.plan_length,
+            steps: result.trace
+                .into_iter()
+                .map(|f| PlanStep { transition: f.transition, state: f.state })
+                .collect(),
+            time: result.time_to_solve,
+            max_steps,
+        })
+    })
+    .await
+    .map_err(|e| Error::Planner { message: e.to_string() })?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(v: i32) -> SPState {
+        SPState::new_from_values(&[(SPPath::from_string("r/pos"), SPValue::Int32(v))])
+    }
+
+    #[test]
+    fn each_step_starts_where_the_previous_one_ended() {
+        let plan = Plan {
+            found: true,
+            length: 2,
+            steps: vec![
+                PlanStep { transition: SPPath::from_string("r/up"), state: state(1) },
+                PlanStep { transition: SPPath::from_string("r/up"), state: state(2) },
+            ],
+            time: Duration::from_millis(1),
+            max_steps: 10,
+        };
+        let from = state(0);
+        let pos = SPPath::from_string("r/pos");
+        let before: Vec<_> = plan.states_before(&from)
+            .iter()
+            .map(|s| s.sp_value_from_path(&pos).cloned())
+            .collect();
+        assert_eq!(before, vec![Some(SPValue::Int32(0)), Some(SPValue::Int32(1))]);
+    }
+}
@@ -0,0 +1,301 @@
+use iced::{button, pick_list, text_input, Alignment, Button, Column, Element, Length, PickList, Row,
+           Space, Text, TextInput};
+use sp_domain::*;
+use std::collections::HashMap;
+use crate::components::{model_variables, parse_value};
+use crate::Message;
+
+const KINDS: [&str; 6] = ["AND", "OR", "NOT", "EQ", "NEQ", "TRUE"];
+
+/// The variables of a model by path, and their sorted names for pick lists.
+pub fn variables_of(model: &Model) -> (HashMap<SPPath, Variable>, Vec<String>) {
+    let variables: HashMap<SPPath, Variable> = model_variables(model)
+        .map(|v| (v.path.clone(), v.clone()))
+        .collect();
+    let mut names: Vec<String> = variables.keys().map(|p| p.to_string()).collect();
+    names.sort();
+    (variables, names)
+}
+
+/// Edits of one node, addressed by node id.
+#[derive(Debug, Clone)]
+pub enum PredicateEdit {
+    Kind(usize, String),
+    AddChild(usize),
+    Remove(usize),
+    Variable(usize, String),
+    Value(usize, String),
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    kind: String,
+    children: Vec<usize>,
+    variable: Option<SPPath>,
+    value: String,
+
+    kind_state: pick_list::State<String>,
+    variable_state: pick_list::State<String>,
+    value_pick_state: pick_list::State<String>,
+    value_input_state: text_input::State,
+    add_button: button::State,
+    remove_button: button::State,
+}
+
+impl Node {
+    fn new(kind: &str) -> Self {
+        Node {
+            kind: kind.to_string(),
+            ..Node::default()
+        }
+    }
+}
+
+/// A predicate being edited, kept as a flat map of nodes so every
+/// node can own its widget state.
+#[derive(Debug)]
+pub struct PredicateEditor {
+    nodes: HashMap<usize, Node>,
+    next_id: usize,
+    root: usize,
+}
+
+impl PredicateEditor {
+    pub fn new(kind: &str) -> Self {
+        let mut e = PredicateEditor {
+            nodes: HashMap::new(),
+            next_id: 0,
+            root: 0,
+        };
+        e.root = e.add(kind);
+        e
+    }
+
+    fn add(&mut self, kind: &str) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.nodes.insert(id, Node::new(kind));
+        if kind == "NOT" {
+            let child = self.add("EQ");
+            self.nodes.get_mut(&id).unwrap().children.push(child);
+        }
+        id
+    }
+
+    fn remove(&mut self, id: usize) {
+        if let Some(node) = self.nodes.remove(&id) {
+            for c in node.children {
+                self.remove(c);
+            }
+        }
+        for n in self.nodes.values_mut() {
+            n.children.retain(|c| *c != id);
+        }
+    }
+
+    fn set_kind(&mut self, id: usize, kind: String) {
+        let children = match self.nodes.get_mut(&id) {
+            Some(n) => {
+                n.kind = kind.clone();
+                std::mem::take(&mut n.children)
+            },
+            None => return,
+        };
+        // keep the children for AND/OR, NOT takes the first one.
+        let keep = match kind.as_str() {
+            "AND" | "OR" => children.len(),
+            "NOT" => 1,
+            _ => 0,
+        };
+        let (kept, dropped) = children.split_at(keep.min(children.len()));
+        for c in dropped {
+            self.remove(*c);
+        }
+        let mut kept = kept.to_vec();
+        if kind == "NOT" && kept.is_empty() {
+            kept.push(self.add("EQ"));
+        }
+        self.nodes.get_mut(&id).unwrap().children = kept;
+    }
+
+    /// (id, depth) in display order.
+    fn rows(&self) -> Vec<(usize, usize)> {
+        fn walk(e: &PredicateEditor, id: usize, depth: usize, rows: &mut Vec<(usize, usize)>) {
+            rows.push((id, depth));
+            if let Some(n) = e.nodes.get(&id) {
+                for c in &n.children {
+                    walk(e, *c, depth + 1, rows);
+                }
+            }
+        }
+        let mut rows = vec![];
+        walk(self, self.root, 0, &mut rows);
+        rows
+    }
+
+    /// The predicate, or what is missing to make one.
+    pub fn build(&self, variables: &HashMap<SPPath, Variable>) -> Result<Predicate, String> {
+        self.build_node(self.root, variables)
+    }
+
+    fn build_node(&self, id: usize, variables: &HashMap<SPPath, Variable>) -> Result<Predicate, String> {
+        let n = self.nodes.get(&id).ok_or("missing node")?;
+        let children = || n.children.iter().map(|c| self.build_node(*c, variables)).collect::<Result<Vec<_>, _>>();
+        let compare = || -> Result<(PredicateValue, PredicateValue), String> {
+            let path = n.variable.clone().ok_or(format!("{} without a variable", n.kind))?;
+            let v = variables.get(&path).ok_or(format!("{} is not in the model", path))?;
+            let value = parse_value(&n.value, v.value_type, &v.domain)
+                .map_err(|e| format!("{}: {}", path, e))?;
+            Ok((PredicateValue::SPPath(path, None), PredicateValue::SPValue(value)))
+        };
+        match n.kind.as_str() {
+            "AND" => Ok(Predicate::AND(children()?)),
+            "OR" => Ok(Predicate::OR(children()?)),
+            "NOT" => {
+                let mut c = children()?;
+                let c = c.pop().ok_or("NOT without a child")?;
+                Ok(Predicate::NOT(Box::new(c)))
+            },
+            "EQ" => compare().map(|(a, b)| Predicate::EQ(a, b)),
+            "NEQ" => compare().map(|(a, b)| Predicate::NEQ(a, b)),
+            _ => Ok(Predicate::TRUE),
+        }
+    }
+
+    pub fn update(&mut self, edit: PredicateEdit) {
+        match edit {
+            PredicateEdit::Kind(id, kind) => self.set_kind(id, kind),
+            PredicateEdit::AddChild(id) => {
+                let child = self.add("EQ");
+                if let Some(n) = self.nodes.get_mut(&id) {
+                    n.children.push(child);
+                }
+            },
+            PredicateEdit::Remove(id) => {
+                if id != self.root {
+                    self.remove(id)
+                }
+            },
+            PredicateEdit::Variable(id, path) => {
+                if let Some(n) = self.nodes.get_mut(&id) {
+                    n.variable = Some(SPPath::from_string(&path));
+                    n.value.clear();
+                }
+            },
+            PredicateEdit::Value(id, value) => {
+                if let Some(n) = self.nodes.get_mut(&id) {
+                    n.value = value;
+                }
+            },
+        }
+    }
+
+    /// One row per node, `on_edit` wraps the edits into the message of the owner.
+    pub fn view<'a>(&'a mut self, on_edit: fn(PredicateEdit) -> Message, variable_names: &[String],
+                    variables: &HashMap<SPPath, Variable>) -> Element<'a, Message> {
+        let rows = self.rows();
+        let root = self.root;
+        let mut nodes: HashMap<&usize, &mut Node> = self.nodes.iter_mut().collect();
+
+        rows.into_iter()
+            .fold(Column::new().spacing(5), |col, (id, depth)| {
+                let n = match nodes.remove(&id) {
+                    Some(n) => n,
+                    None => return col,
+                };
+                let kinds = KINDS.iter().map(|k| k.to_string()).collect();
+                let row = Row::new()
+                    .spacing(10)
+                    .align_items(Alignment::Center)
+                    .push(Space::with_width(Length::Units((depth * 20) as u16)))
+                    .push(PickList::new(&mut n.kind_state, kinds, Some(n.kind.clone()),
+                                        move |k| on_edit(PredicateEdit::Kind(id, k))));
+                let row = match n.kind.as_str() {
+                    "AND" | "OR" => row.push(Button::new(&mut n.add_button, Text::new("+").size(14))
+                                             .on_press(on_edit(PredicateEdit::AddChild(id)))),
+                    "EQ" | "NEQ" => {
+                        let row = row.push(PickList::new(&mut n.variable_state, variable_names.to_vec(),
+                                                         n.variable.as_ref().map(|p| p.to_string()),
+                                                         move |p| on_edit(PredicateEdit::Variable(id, p))));
+                        let domain = n.variable.as_ref()
+                            .and_then(|p| variables.get(p))
+                            .map(|v| v.domain.iter().map(|d| d.to_string()).collect::<Vec<_>>())
+                            .unwrap_or_default();
+                        let on_value = move |v| on_edit(PredicateEdit::Value(id, v));
+                        if domain.is_empty() {
+                            row.push(TextInput::new(&mut n.value_input_state, "value", &n.value, on_value)
+                                     .padding(5)
+                                     .width(Length::Units(120)))
+                        } else {
+                            let selected = if n.value.is_empty() { None } else { Some(n.value.clone()) };
+                            row.push(PickList::new(&mut n.value_pick_state, domain, selected, on_value))
+                        }
+                    },
+                    _ => row,
+                };
+                let row = if id != root {
+                    row.push(Button::new(&mut n.remove_button, Text::new("x").size(14))
+                             .on_press(on_edit(PredicateEdit::Remove(id))))
+                } else {
+                    row
+                };
+                col.push(row)
+            })
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_models;
+
+    fn eq(path: &str, value: SPValue) -> Predicate {
+        Predicate::EQ(PredicateValue::SPPath(test_models::path(path), None), PredicateValue::SPValue(value))
+    }
+
+    #[test]
+    fn build_nested_predicate() {
+        let (variables, _) = variables_of(&test_models::model());
+        let mut e = PredicateEditor::new("AND");
+        e.update(PredicateEdit::AddChild(0));
+        e.update(PredicateEdit::Variable(1, "r/b".into()));
+        e.update(PredicateEdit::Value(1, "true".into()));
+        e.update(PredicateEdit::AddChild(0));
+        // NOT gets an EQ child of its own.
+        e.update(PredicateEdit::Kind(2, "NOT".into()));
+        e.update(PredicateEdit::Variable(3, "r/pos".into()));
+        e.update(PredicateEdit::Value(3, "1".into()));
+
+        let expected = Predicate::AND(vec![
+            eq("r/b", SPValue::Bool(true)),
+            Predicate::NOT(Box::new(eq("r/pos", SPValue::Int32(1)))),
+        ]);
+        assert_eq!(e.build(&variables), Ok(expected));
+    }
+
+    #[test]
+    fn build_says_what_is_missing() {
+        let (variables, _) = variables_of(&test_models::model());
+        let mut e = PredicateEditor::new("AND");
+        e.update(PredicateEdit::AddChild(0));
+        assert_eq!(e.build(&variables), Err("EQ without a variable".to_string()));
+        e.update(PredicateEdit::Variable(1, "r/pos".into()));
+        e.update(PredicateEdit::Value(1, "5".into()));
+        assert_eq!(e.build(&variables), Err("r/pos: 5 not in domain [0, 1, 2]".to_string()));
+
+        // removing the bad child leaves an empty AND.
+        e.update(PredicateEdit::Remove(1));
+        assert_eq!(e.build(&variables), Ok(Predicate::AND(vec![])));
+    }
+
+    #[test]
+    fn changing_kind_drops_children() {
+        let (variables, _) = variables_of(&test_models::model());
+        let mut e = PredicateEditor::new("AND");
+        e.update(PredicateEdit::AddChild(0));
+        e.update(PredicateEdit::Kind(0, "TRUE".into()));
+        assert_eq!(e.rows(), vec![(0, 0)]);
+        assert_eq!(e.build(&variables), Ok(Predicate::TRUE));
+    }
+}
@@ -0,0 +1,323 @@
+use iced::{button, slider, text_input, Alignment, Button, Column, Element, Length, Row, Slider, Text, TextInput};
+use serde::{Deserialize, Serialize};
+use sp_domain::*;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::{Error, Message};
+
+/// One line in a recording file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedState {
+    /// milliseconds since the unix epoch
+    pub time: u64,
+    pub state: serde_json::Value,
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Appends every received state to a json lines file.
+pub struct Recorder {
+    pub path: String,
+    pub count: usize,
+    file: std::fs::File,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Result<Recorder, Error> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| Error::io(&format!("could not open {}", path), e))?;
+        Ok(Recorder {
+            path: path.to_string(),
+            count: 0,
+            file,
+        })
+    }
+
+    pub fn record(&mut self, state: &SPState) -> Result<(), Error> {
+        let line = RecordedState {
+            time: now_millis(),
+            state: SPStateJson::from_state_flat(state).to_json(),
+        };
+        let json = serde_json::to_string(&line)?;
+        writeln!(self.file, "{}", json)?;
+        self.count += 1;
+        Ok(())
+    }
+}
+
+/// A loaded recording that is fed through `Message::NewState`
+/// instead of the live state stream.
+pub struct Replay {
+    pub path: String,
+    /// (milliseconds since the first frame, state)
+    frames: Vec<(u64, SPState)>,
+    position: usize,
+    cursor: f64,
+    playing: bool,
+    speed: f32,
+    last_tick: Instant,
+
+    play_button: button::State,
+    slower_button: button::State,
+    faster_button: button::State,
+    stop_button: button::State,
+    timeline: slider::State,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Replay, Error> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| Error::io(&format!("could not open {}", path), e))?;
+        let mut frames = vec![];
+        for (i, line) in std::io::BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let rs: RecordedState = Error::parse_json(&format!("{} line {}", path, i + 1), &line)?;
+            let s: SPStateJson = serde_json::from_value(rs.state)?;
+            frames.push((rs.time, s.to_state()));
+        }
+        let start = frames.first().map(|(t, _)| *t).unwrap_or(0);
+        let frames = frames.into_iter().map(|(t, s)| (t.saturating_sub(start), s)).collect();
+        Ok(Replay::new(path, frames))
+    }
+
+    fn new(path: &str, frames: Vec<(u64, SPState)>) -> Replay {
+        Replay {
+            path: path.to_string(),
+            frames,
+            position: 0,
+            cursor: 0.0,
+            playing: false,
+            speed: 1.0,
+            last_tick: Instant::now(),
+            play_button: button::State::new(),
+            slower_button: button::State::new(),
+            faster_button: button::State::new(),
+            stop_button: button::State::new(),
+            timeline: slider::State::new(),
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn current(&self) -> Option<SPState> {
+        self.frames.get(self.position).map(|(_, s)| s.clone())
+    }
+
+    /// Play from the start again if we are at the end, that state is
+    /// returned so it can be shown.
+    pub fn play_pause(&mut self) -> Option<SPState> {
+        self.last_tick = Instant::now();
+        if self.playing {
+            self.playing = false;
+            return None;
+        }
+        if self.frames.len() < 2 {
+            return None;
+        }
+        self.playing = true;
+        if self.position + 1 >= self.frames.len() {
+            self.seek(0)
+        } else {
+            None
+        }
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.max(0.125).min(16.0);
+    }
+
+    pub fn seek(&mut self, position: usize) -> Option<SPState> {
+        self.position = position.min(self.frames.len().saturating_sub(1));
+        self.cursor = self.frames.get(self.position).map(|(t, _)| *t as f64).unwrap_or(0.0);
+        self.current()
+    }
+
+    /// Move the play cursor forward by the time since the last tick.
+    /// Returns the newest state if we passed any frames.
+    pub fn tick(&mut self) -> Option<SPState> {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_tick);
+        self.last_tick = now;
+        if !self.playing {
+            return None;
+        }
+
+        self.cursor += elapsed.as_secs_f64() * 1000.0 * self.speed as f64;
+        let start = self.position;
+        while self.position + 1 < self.frames.len() && self.frames[self.position + 1].0 as f64 <= self.cursor {
+            self.position += 1;
+        }
+        // also stops an empty recording, which has no last frame.
+        if self.position + 1 >= self.frames.len() {
+            self.playing = false;
+        }
+        if self.position != start {
+            self.current()
+        } else {
+            None
+        }
+    }
+
+    pub fn view(&mut self) -> Element<Message> {
+        let total = self.frames.last().map(|(t, _)| *t).unwrap_or(0);
+        let at = self.frames.get(self.position).map(|(t, _)| *t).unwrap_or(0);
+        let max = self.frames.len().saturating_sub(1) as u32;
+
+        Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(Button::new(&mut self.play_button,
+                              Text::new(if self.playing { "Pause" } else { "Play" }).size(14))
+                  .on_press(Message::ReplayPlayPause))
+            .push(Button::new(&mut self.slower_button, Text::new("<<").size(14))
+                  .on_press(Message::ReplaySpeed(self.speed / 2.0)))
+            .push(Text::new(format!("{}x", self.speed)).size(14))
+            .push(Button::new(&mut self.faster_button, Text::new(">>").size(14))
+                  .on_press(Message::ReplaySpeed(self.speed * 2.0)))
+            .push(Slider::new(&mut self.timeline, 0..=max, self.position as u32,
+                              |p| Message::ReplaySeek(p as usize))
+                  .width(Length::Fill))
+            .push(Text::new(format!("{:.1} / {:.1} s",
+                                    Duration::from_millis(at).as_secs_f32(),
+                                    Duration::from_millis(total).as_secs_f32())).size(14))
+            .push(Button::new(&mut self.stop_button, Text::new("Live").size(14))
+                  .on_press(Message::StopReplay))
+            .into()
+    }
+}
+
+/// Controls for starting recordings and loading replays.
+pub struct Recording {
+    pub recorder: Option<Recorder>,
+    pub record_path: String,
+    pub replay_path: String,
+
+    record_path_state: text_input::State,
+    replay_path_state: text_input::State,
+    record_button: button::State,
+    load_button: button::State,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Recording {
+            recorder: None,
+            record_path: format!("sp_states_{}.jsonl", now_millis() / 1000),
+            replay_path: String::new(),
+            record_path_state: text_input::State::new(),
+            replay_path_state: text_input::State::new(),
+            record_button: button::State::new(),
+            load_button: button::State::new(),
+        }
+    }
+
+    pub fn view(&mut self) -> Element<Message> {
+        let record_text = match &self.recorder {
+            Some(r) => format!("Stop recording ({} states)", r.count),
+            None => "Start recording".to_string(),
+        };
+        Column::new()
+            .spacing(10)
+            .push(Text::new("Record").size(20))
+            .push(Row::new()
+                  .spacing(10)
+                  .push(TextInput::new(&mut self.record_path_state, "recording.jsonl",
+                                       &self.record_path, Message::RecordPathChanged)
+                        .padding(5))
+                  .push(Button::new(&mut self.record_button, Text::new(record_text))
+                        .padding(8)
+                        .style(crate::style::Button::Primary)
+                        .on_press(Message::ToggleRecording)))
+            .push(Text::new("Replay").size(20))
+            .push(Row::new()
+                  .spacing(10)
+                  .push(TextInput::new(&mut self.replay_path_state, "recording.jsonl",
+                                       &self.replay_path, Message::ReplayPathChanged)
+                        .padding(5))
+                  .push(Button::new(&mut self.load_button, Text::new("Load"))
+                        .padding(8)
+                        .style(crate::style::Button::Primary)
+                        .on_press(Message::StartReplay)))
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames at 0, 100, 200 ms where `x` is the frame number.
+    fn replay() -> Replay {
+        let frames = (0..3)
+            .map(|i| {
+                let s = SPState::new_from_values(&[(SPPath::from_string("x"), SPValue::Int32(i))]);
+                (i as u64 * 100, s)
+            })
+            .collect();
+        Replay::new("test", frames)
+    }
+
+    fn x(s: Option<SPState>) -> Option<SPValue> {
+        s.and_then(|s| s.sp_value_from_path(&SPPath::from_string("x")).cloned())
+    }
+
+    /// Tick as if `ms` milliseconds passed since the last one.
+    fn tick_after(r: &mut Replay, ms: u64) -> Option<SPState> {
+        r.last_tick = Instant::now() - Duration::from_millis(ms);
+        r.tick()
+    }
+
+    #[test]
+    fn tick_moves_with_time_and_stops_at_the_end() {
+        let mut r = replay();
+        assert!(tick_after(&mut r, 150).is_none(), "paused");
+        r.play_pause();
+        assert_eq!(x(tick_after(&mut r, 150)), Some(SPValue::Int32(1)));
+        assert!(tick_after(&mut r, 10).is_none());
+        assert_eq!(x(tick_after(&mut r, 500)), Some(SPValue::Int32(2)));
+        assert!(!r.is_playing());
+    }
+
+    #[test]
+    fn play_at_the_end_rewinds() {
+        let mut r = replay();
+        r.seek(2);
+        assert_eq!(x(r.play_pause()), Some(SPValue::Int32(0)));
+        assert!(r.is_playing());
+        assert!(r.play_pause().is_none());
+        assert!(!r.is_playing());
+    }
+
+    #[test]
+    fn empty_recording_never_plays() {
+        let mut r = Replay::new("empty", vec![]);
+        assert!(r.play_pause().is_none());
+        assert!(!r.is_playing());
+        assert!(r.seek(3).is_none());
+        r.playing = true;
+        assert!(tick_after(&mut r, 100).is_none());
+        assert!(!r.is_playing());
+    }
+
+    #[test]
+    fn seek_clamps_and_moves_the_cursor() {
+        let mut r = replay();
+        assert_eq!(x(r.seek(1)), Some(SPValue::Int32(1)));
+        assert_eq!(r.cursor, 100.0);
+        assert_eq!(x(r.seek(10)), Some(SPValue::Int32(2)));
+        assert_eq!(r.position, 2);
+    }
+}
@@ -0,0 +1,262 @@
+use serde::Deserialize;
+use sp_domain::*;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use crate::backend::StateUpdate;
+use crate::state_store::StateStore;
+
+/// What to do when states arrive faster than the ui takes them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ArgEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelMode {
+    /// only keep the newest state
+    Latest,
+    /// queue every state, merging into the newest queued state when the queue is full
+    Lossless,
+}
+
+impl std::fmt::Display for ChannelMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChannelMode::Latest => write!(f, "latest only"),
+            ChannelMode::Lossless => write!(f, "lossless"),
+        }
+    }
+}
+
+struct Shared {
+    mode: ChannelMode,
+    capacity: usize,
+    queue: Mutex<VecDeque<StateUpdate>>,
+    notify: Notify,
+    /// states coalesced or merged away before the ui saw them
+    dropped: Arc<AtomicUsize>,
+    /// live senders, the receiver is done once they are all gone
+    senders: AtomicUsize,
+}
+
+pub struct StateSender {
+    shared: Arc<Shared>,
+}
+
+pub struct StateReceiver {
+    shared: Arc<Shared>,
+}
+
+/// Replaces the capacity-1 mpsc channel, which silently lost bursts of
+/// states. Sending never blocks; whatever could not be kept as is gets
+/// counted.
+pub fn state_channel(mode: ChannelMode, capacity: usize) -> (StateSender, StateReceiver) {
+    let shared = Arc::new(Shared {
+        mode,
+        capacity: capacity.max(1),
+        queue: Mutex::new(VecDeque::new()),
+        notify: Notify::new(),
+        dropped: Arc::new(AtomicUsize::new(0)),
+        senders: AtomicUsize::new(1),
+    });
+    (StateSender { shared: shared.clone() }, StateReceiver { shared })
+}
+
+impl StateSender {
+    pub fn send(&self, update: StateUpdate) {
+        let shared = &self.shared;
+        let mut queue = shared.queue.lock().unwrap();
+        let dropped = match (shared.mode, update) {
+            // parse errors are rare, keep them around so they are reported.
+            (ChannelMode::Latest, StateUpdate::State(s)) => {
+                let before = queue.len();
+                queue.retain(|u| !matches!(u, StateUpdate::State(_)));
+                let coalesced = before - queue.len();
+                queue.push_back(StateUpdate::State(s));
+                coalesced
+            },
+            (_, update) if queue.len() < shared.capacity => {
+                queue.push_back(update);
+                0
+            },
+            // full, fold the new state into the last queued one so no
+            // value is lost. Merging into a state before a parse error would
+            // reorder the stream, so then go past the bound instead.
+            (ChannelMode::Lossless, StateUpdate::State(s)) => match queue.back_mut() {
+                Some(StateUpdate::State(queued)) => {
+                    *queued = merge(queued, &s);
+                    1
+                },
+                _ => {
+                    queue.push_back(StateUpdate::State(s));
+                    0
+                },
+            },
+            (_, StateUpdate::ParseError(..)) => 1,
+        };
+        drop(queue);
+        if dropped > 0 {
+            shared.dropped.fetch_add(dropped, Ordering::Relaxed);
+        }
+        shared.notify.notify_one();
+    }
+}
+
+impl Clone for StateSender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        StateSender { shared: self.shared.clone() }
+    }
+}
+
+impl Drop for StateSender {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // wake the receiver so it sees the channel is closed.
+            self.shared.notify.notify_one();
+        }
+    }
+}
+
+impl StateReceiver {
+    /// Updates in the order they were sent. Waits until there is one, or
+    /// gives None once the queue is empty and every sender is gone.
+    pub async fn recv(&mut self) -> Option<StateUpdate> {
+        loop {
+            if let Some(update) = self.shared.queue.lock().unwrap().pop_front() {
+                return Some(update);
+            }
+            if self.shared.senders.load(Ordering::Acquire) == 0 {
+                return None;
+            }
+            self.shared.notify.notified().await;
+        }
+    }
+
+    pub fn mode(&self) -> ChannelMode {
+        self.shared.mode
+    }
+
+    /// Shared with the sender, so it can be read after the receiver has
+    /// been moved into the subscription.
+    pub fn dropped_counter(&self) -> Arc<AtomicUsize> {
+        self.shared.dropped.clone()
+    }
+}
+
+/// `newer` applied on top of `older`.
+fn merge(older: &SPState, newer: &SPState) -> SPState {
+    let mut store: StateStore<(SPPath, SPValue)> = StateStore::default();
+    store.update_from(older);
+    store.update_from(newer);
+    store.to_state()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(values: &[(&str, i32)]) -> StateUpdate {
+        let values: Vec<_> = values.iter().map(|(p, v)| (SPPath::from_string(p), SPValue::Int32(*v))).collect();
+        StateUpdate::State(SPState::new_from_values(&values))
+    }
+
+    fn parse_error() -> StateUpdate {
+        StateUpdate::ParseError("bad".into(), "{".into())
+    }
+
+    /// What is queued, states as "a=1,b=2" and parse errors as "error".
+    fn queued(receiver: &StateReceiver) -> Vec<String> {
+        receiver.shared.queue.lock().unwrap()
+            .iter()
+            .map(|u| match u {
+                StateUpdate::State(s) => {
+                    let mut values: Vec<_> = s.projection().state
+                        .iter()
+                        .map(|(p, v)| format!("{}={}", p, v.value()))
+                        .collect();
+                    values.sort();
+                    values.join(",")
+                },
+                StateUpdate::ParseError(..) => "error".to_string(),
+            })
+            .collect()
+    }
+
+    fn dropped(receiver: &StateReceiver) -> usize {
+        receiver.dropped_counter().load(Ordering::Relaxed)
+    }
+
+    #[test]
+    fn latest_coalesces_states_but_keeps_parse_errors() {
+        let (sender, receiver) = state_channel(ChannelMode::Latest, 10);
+        sender.send(state(&[("a", 1)]));
+        sender.send(parse_error());
+        sender.send(state(&[("a", 2)]));
+        sender.send(state(&[("a", 3)]));
+        assert_eq!(queued(&receiver), vec!["error", "a=3"]);
+        assert_eq!(dropped(&receiver), 2);
+    }
+
+    #[test]
+    fn lossless_queues_in_order_and_merges_when_full() {
+        let (sender, receiver) = state_channel(ChannelMode::Lossless, 2);
+        sender.send(state(&[("a", 1)]));
+        sender.send(state(&[("a", 2), ("b", 1)]));
+        assert_eq!(dropped(&receiver), 0);
+        sender.send(state(&[("a", 3)]));
+        assert_eq!(queued(&receiver), vec!["a=1", "a=3,b=1"]);
+        assert_eq!(dropped(&receiver), 1);
+    }
+
+    #[test]
+    fn lossless_keeps_stream_order_after_a_trailing_parse_error() {
+        let (sender, receiver) = state_channel(ChannelMode::Lossless, 3);
+        sender.send(state(&[("a", 1)]));
+        sender.send(state(&[("b", 1)]));
+        sender.send(parse_error());
+        sender.send(state(&[("b", 2)]));
+        assert_eq!(queued(&receiver), vec!["a=1", "b=1", "error", "b=2"]);
+        assert_eq!(dropped(&receiver), 0);
+
+        // the newest update is a state again, so the next one merges into it.
+        sender.send(state(&[("c", 1)]));
+        assert_eq!(queued(&receiver), vec!["a=1", "b=1", "error", "b=2,c=1"]);
+        assert_eq!(dropped(&receiver), 1);
+
+        // more parse errors than room are dropped and counted.
+        sender.send(parse_error());
+        assert_eq!(dropped(&receiver), 2);
+    }
+
+    #[test]
+    fn lossless_goes_past_the_bound_rather_than_lose_a_state() {
+        let (sender, receiver) = state_channel(ChannelMode::Lossless, 1);
+        sender.send(parse_error());
+        sender.send(state(&[("a", 1)]));
+        assert_eq!(queued(&receiver), vec!["error", "a=1"]);
+        assert_eq!(dropped(&receiver), 0);
+    }
+
+    #[tokio::test]
+    async fn recv_gives_updates_in_order() {
+        let (sender, mut receiver) = state_channel(ChannelMode::Lossless, 10);
+        sender.send(state(&[("a", 1)]));
+        sender.send(parse_error());
+        assert!(matches!(receiver.recv().await, Some(StateUpdate::State(_))));
+        assert!(matches!(receiver.recv().await, Some(StateUpdate::ParseError(..))));
+    }
+
+    #[tokio::test]
+    async fn recv_ends_once_all_senders_are_gone() {
+        let (sender, mut receiver) = state_channel(ChannelMode::Lossless, 10);
+        let other = sender.clone();
+        sender.send(state(&[("a", 1)]));
+        drop(sender);
+        assert!(matches!(receiver.recv().await, Some(StateUpdate::State(_))));
+
+        // a sender is still alive, so the receiver waits for it.
+        let wait = std::time::Duration::from_millis(50);
+        assert!(tokio::time::timeout(wait, receiver.recv()).await.is_err());
+        drop(other);
+        assert!(receiver.recv().await.is_none());
+    }
+}
@@ -0,0 +1,185 @@
+use sp_domain::*;
+use std::collections::{HashMap, HashSet};
+
+/// Something the store can keep per variable.
+pub trait StateEntry {
+    fn new(path: SPPath, value: SPValue) -> Self;
+    fn path(&self) -> &SPPath;
+    fn value(&self) -> &SPValue;
+    fn set_value(&mut self, value: SPValue);
+}
+
+impl StateEntry for (SPPath, SPValue) {
+    fn new(path: SPPath, value: SPValue) -> Self {
+        (path, value)
+    }
+
+    fn path(&self) -> &SPPath {
+        &self.0
+    }
+
+    fn value(&self) -> &SPValue {
+        &self.1
+    }
+
+    fn set_value(&mut self, value: SPValue) {
+        self.1 = value;
+    }
+}
+
+/// State entries sorted by path, with an index for lookups. Keeps track
+/// of which entries the last update touched.
+#[derive(Debug, Clone)]
+pub struct StateStore<T> {
+    entries: Vec<T>,
+    index: HashMap<SPPath, usize>,
+    changed: HashSet<SPPath>,
+}
+
+impl<T> Default for StateStore<T> {
+    fn default() -> Self {
+        StateStore {
+            entries: vec![],
+            index: HashMap::new(),
+            changed: HashSet::new(),
+        }
+    }
+}
+
+impl<T: StateEntry> StateStore<T> {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, path: &SPPath) -> Option<&T> {
+        self.index.get(path).map(|i| &self.entries[*i])
+    }
+
+    pub fn get_mut(&mut self, path: &SPPath) -> Option<&mut T> {
+        match self.index.get(path) {
+            Some(i) => Some(&mut self.entries[*i]),
+            None => None,
+        }
+    }
+
+    pub fn value(&self, path: &SPPath) -> Option<&SPValue> {
+        self.get(path).map(|e| e.value())
+    }
+
+    /// In path order.
+    pub fn iter(&self) -> std::slice::Iter<T> {
+        self.entries.iter()
+    }
+
+    /// In path order. Don't change the paths.
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<T> {
+        self.entries.iter_mut()
+    }
+
+    /// Paths that were added or got a new value in the last update.
+    pub fn changed(&self) -> &HashSet<SPPath> {
+        &self.changed
+    }
+
+    /// Merge new values. Only re-sorts when new paths show up.
+    pub fn update(&mut self, values: impl IntoIterator<Item = (SPPath, SPValue)>) -> &HashSet<SPPath> {
+        self.changed.clear();
+        let mut added = vec![];
+        for (path, value) in values {
+            match self.index.get(&path) {
+                Some(i) => {
+                    let e = &mut self.entries[*i];
+                    if e.value() != &value {
+                        e.set_value(value);
+                        self.changed.insert(path);
+                    }
+                },
+                None => added.push(T::new(path, value)),
+            }
+        }
+
+        if !added.is_empty() {
+            self.changed.extend(added.iter().map(|e| e.path().clone()));
+            self.entries.extend(added);
+            self.entries.sort_by(|a, b| a.path().cmp(b.path()));
+            self.index = self.entries
+                .iter()
+                .enumerate()
+                .map(|(i, e)| (e.path().clone(), i))
+                .collect();
+        }
+        &self.changed
+    }
+
+    pub fn update_from(&mut self, state: &SPState) -> &HashSet<SPPath> {
+        self.update(state.projection().state
+                    .iter()
+                    .map(|(p, v)| ((*p).clone(), v.value().clone())))
+    }
+
+    pub fn to_state(&self) -> SPState {
+        let values: Vec<_> = self.entries.iter().map(|e| (e.path().clone(), e.value().clone())).collect();
+        SPState::new_from_values(&values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Store = StateStore<(SPPath, SPValue)>;
+
+    fn values(vs: &[(&str, i32)]) -> Vec<(SPPath, SPValue)> {
+        vs.iter().map(|(p, v)| (SPPath::from_string(p), SPValue::Int32(*v))).collect()
+    }
+
+    fn paths(store: &Store) -> Vec<String> {
+        store.iter().map(|e| e.0.to_string()).collect()
+    }
+
+    fn changed(store: &Store) -> Vec<String> {
+        let mut changed: Vec<_> = store.changed().iter().map(|p| p.to_string()).collect();
+        changed.sort();
+        changed
+    }
+
+    #[test]
+    fn entries_are_sorted_and_indexed() {
+        let mut store = Store::default();
+        store.update(values(&[("b/x", 1), ("a/y", 2)]));
+        store.update(values(&[("a/a", 3)]));
+        assert_eq!(paths(&store), vec!["a/a", "a/y", "b/x"]);
+        assert_eq!(store.len(), 3);
+        for (p, v) in values(&[("a/a", 3), ("a/y", 2), ("b/x", 1)]) {
+            assert_eq!(store.value(&p), Some(&v));
+        }
+        assert_eq!(store.value(&SPPath::from_string("c")), None);
+    }
+
+    #[test]
+    fn changed_has_only_new_and_different_values() {
+        let mut store = Store::default();
+        store.update(values(&[("a", 1), ("b", 2)]));
+        assert_eq!(changed(&store), vec!["a", "b"]);
+
+        store.update(values(&[("a", 1), ("b", 5), ("c", 0)]));
+        assert_eq!(changed(&store), vec!["b", "c"]);
+
+        // an update without differences clears the set.
+        assert!(store.update(values(&[("a", 1)])).is_empty());
+    }
+
+    #[test]
+    fn to_state_round_trips() {
+        let mut store = Store::default();
+        store.update(values(&[("a", 1), ("b", 2)]));
+        let mut other = Store::default();
+        other.update_from(&store.to_state());
+        assert_eq!(paths(&other), vec!["a", "b"]);
+        assert_eq!(other.value(&SPPath::from_string("b")), Some(&SPValue::Int32(2)));
+    }
+}
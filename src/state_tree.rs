@@ -0,0 +1,84 @@
+use sp_domain::SPPath;
+use std::collections::{HashMap, HashSet};
+
+/// One visible line in the state tree.
+#[derive(Debug, Clone)]
+pub enum TreeRow {
+    /// A path prefix shared by one or more variables.
+    Group { path: SPPath, depth: usize, matching: usize },
+    /// A variable, by index into the sorted state.
+    Leaf { index: usize, depth: usize },
+}
+
+pub fn prefix(path: &SPPath, len: usize) -> SPPath {
+    SPPath::from_string(&path.path[..len].join("/"))
+}
+
+/// Compute the visible rows of the state tree. `paths` must be sorted and
+/// `matches` says which of them pass the filter. Groups without any
+/// matching variable are left out, children of collapsed groups are hidden.
+pub fn tree_rows(paths: &[&SPPath], matches: &[bool], expanded: &HashSet<SPPath>) -> Vec<TreeRow> {
+    let mut counts: HashMap<SPPath, usize> = HashMap::new();
+    for (p, _) in paths.iter().zip(matches).filter(|(_, m)| **m) {
+        for len in 1..p.path.len() {
+            *counts.entry(prefix(p, len)).or_insert(0) += 1;
+        }
+    }
+
+    let mut rows = vec![];
+    // the group segments of the previous visible variable
+    let mut open: Vec<&String> = vec![];
+    for (index, (p, _)) in paths.iter().zip(matches).enumerate().filter(|(_, (_, m))| **m) {
+        let groups = &p.path[..p.path.len().saturating_sub(1)];
+        let shared = open.iter().zip(groups).take_while(|(a, b)| **a == *b).count();
+        open.truncate(shared);
+
+        let mut visible = (1..=shared).all(|len| expanded.contains(&prefix(p, len)));
+        for (depth, segment) in groups.iter().enumerate().skip(shared) {
+            let group = prefix(p, depth + 1);
+            if visible {
+                let matching = counts.get(&group).copied().unwrap_or(0);
+                rows.push(TreeRow::Group { path: group.clone(), depth, matching });
+            }
+            visible = visible && expanded.contains(&group);
+            open.push(segment);
+        }
+
+        if visible {
+            rows.push(TreeRow::Leaf { index, depth: groups.len() });
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(paths: &[&str], matches: &[bool], expanded: &[&str]) -> Vec<String> {
+        let paths: Vec<SPPath> = paths.iter().map(|p| SPPath::from_string(p)).collect();
+        let paths: Vec<&SPPath> = paths.iter().collect();
+        let expanded = expanded.iter().map(|p| SPPath::from_string(p)).collect();
+        tree_rows(&paths, matches, &expanded)
+            .into_iter()
+            .map(|r| match r {
+                TreeRow::Group { path, depth, matching } => format!("{} {} ({})", depth, path, matching),
+                TreeRow::Leaf { index, depth } => format!("{} #{}", depth, index),
+            })
+            .collect()
+    }
+
+    const PATHS: [&str; 4] = ["a/x/1", "a/x/2", "a/y", "b/z"];
+
+    #[test]
+    fn collapsed_groups_hide_their_children() {
+        assert_eq!(rows(&PATHS, &[true; 4], &[]), vec!["0 a (3)", "0 b (1)"]);
+        assert_eq!(rows(&PATHS, &[true; 4], &["a"]), vec!["0 a (3)", "1 a/x (2)", "1 #2", "0 b (1)"]);
+    }
+
+    #[test]
+    fn groups_without_matches_are_left_out() {
+        let matches = [false, true, false, false];
+        assert_eq!(rows(&PATHS, &matches, &["a", "a/x", "b"]), vec!["0 a (1)", "1 a/x (1)", "2 #1"]);
+    }
+}
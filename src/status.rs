@@ -0,0 +1,154 @@
+use iced::{Alignment, Element, Row, Text};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::state_channel::ChannelMode;
+use crate::Message;
+
+/// Which of the runner services answered the last availability check.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServiceStatus {
+    pub get_model: bool,
+    pub set_model: bool,
+    pub set_state: bool,
+}
+
+/// Keeps track of how the connection to the runner is doing.
+#[derive(Debug, Clone)]
+pub struct ConnectionStatus {
+    pub services: Option<ServiceStatus>,
+    pub last_state: Option<Instant>,
+    pub stale_after: Duration,
+    pub parse_errors: usize,
+    last_parse_error_popup: Option<Instant>,
+    /// how the state channel handles bursts, and how many states it
+    /// coalesced or merged away
+    pub channel_mode: Option<ChannelMode>,
+    dropped_states: Arc<AtomicUsize>,
+    // arrival times of the state messages in the rate window
+    arrivals: VecDeque<Instant>,
+}
+
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+const PARSE_ERROR_POPUP_INTERVAL: Duration = Duration::from_secs(10);
+
+impl ConnectionStatus {
+    pub fn new(stale_after: Duration) -> Self {
+        ConnectionStatus {
+            services: None,
+            last_state: None,
+            stale_after,
+            parse_errors: 0,
+            last_parse_error_popup: None,
+            channel_mode: None,
+            dropped_states: Arc::new(AtomicUsize::new(0)),
+            arrivals: VecDeque::new(),
+        }
+    }
+
+    pub fn channel(&mut self, mode: ChannelMode, dropped_states: Arc<AtomicUsize>) {
+        self.channel_mode = Some(mode);
+        self.dropped_states = dropped_states;
+    }
+
+    pub fn dropped_states(&self) -> usize {
+        self.dropped_states.load(Ordering::Relaxed)
+    }
+
+    /// Count a malformed state message. True if it should pop up, which
+    /// is at most once every few seconds.
+    pub fn parse_error(&mut self) -> bool {
+        self.parse_errors += 1;
+        let now = Instant::now();
+        match self.last_parse_error_popup {
+            Some(t) if now.duration_since(t) < PARSE_ERROR_POPUP_INTERVAL => false,
+            _ => {
+                self.last_parse_error_popup = Some(now);
+                true
+            },
+        }
+    }
+
+    pub fn state_received(&mut self) {
+        let now = Instant::now();
+        self.last_state = Some(now);
+        self.arrivals.push_back(now);
+        self.prune(now);
+    }
+
+    fn prune(&mut self, now: Instant) {
+        while let Some(t) = self.arrivals.front() {
+            if now.duration_since(*t) > RATE_WINDOW {
+                self.arrivals.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// State messages per second over the last few seconds.
+    pub fn rate(&mut self) -> f32 {
+        self.prune(Instant::now());
+        self.arrivals.len() as f32 / RATE_WINDOW.as_secs_f32()
+    }
+
+    pub fn is_stale(&self) -> bool {
+        match self.last_state {
+            Some(t) => t.elapsed() > self.stale_after,
+            None => true,
+        }
+    }
+
+    pub fn view(&mut self) -> Element<Message> {
+        let ok = [0.0, 0.5, 0.0];
+        let bad = [0.8, 0.1, 0.1];
+        let unknown = [0.5, 0.5, 0.5];
+
+        let service = |name: &str, available: Option<bool>| {
+            let color = match available {
+                Some(true) => ok,
+                Some(false) => bad,
+                None => unknown,
+            };
+            Text::new(name.to_string()).size(14).color(color)
+        };
+        let services = self.services;
+
+        let last = match self.last_state {
+            Some(t) => format!("last state {:.1} s ago", t.elapsed().as_secs_f32()),
+            None => "no state received".to_string(),
+        };
+        let state_text = if self.is_stale() {
+            Text::new(format!("STALE: {}", last)).size(14).color(bad)
+        } else {
+            Text::new(last).size(14).color(ok)
+        };
+
+        let row = Row::new()
+            .spacing(15)
+            .align_items(Alignment::Center)
+            .push(service("get_model", services.map(|s| s.get_model)))
+            .push(service("set_model", services.map(|s| s.set_model)))
+            .push(service("set_state", services.map(|s| s.set_state)))
+            .push(state_text)
+            .push(Text::new(format!("{:.1} states/s", self.rate())).size(14));
+        let row = match self.channel_mode {
+            Some(ChannelMode::Latest) => row.push(Text::new(format!("{} coalesced", self.dropped_states()))
+                                                  .size(14).color(unknown)),
+            // in lossless mode anything merged away is worth noticing.
+            Some(ChannelMode::Lossless) => {
+                let dropped = self.dropped_states();
+                let color = if dropped > 0 { bad } else { unknown };
+                row.push(Text::new(format!("{} merged (queue full)", dropped)).size(14).color(color))
+            },
+            None => row,
+        };
+        if self.parse_errors > 0 {
+            row.push(Text::new(format!("{} bad state messages", self.parse_errors)).size(14).color(bad))
+                .into()
+        } else {
+            row.into()
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use sp_domain::*;
+use sp_formal::CompiledModel;
+
+/// `r/b` is a bool, `r/pos` an int in 0..3.
+pub fn model() -> Model {
+    let mut model = Model::new("test");
+    let mut r = Resource::new("r");
+    r.variables.push(variable("r/b", SPValueType::Bool, vec![false.to_spvalue(), true.to_spvalue()]));
+    r.variables.push(variable("r/pos", SPValueType::Int32, (0..3).map(SPValue::Int32).collect()));
+    model.resources.push(r);
+    model
+}
+
+pub fn compiled_model() -> CompiledModel {
+    CompiledModel::from(model())
+}
+
+pub fn variable(path: &str, value_type: SPValueType, domain: Vec<SPValue>) -> Variable {
+    let mut v = Variable::new(path, VariableType::Estimated, value_type, domain);
+    v.path = SPPath::from_string(path);
+    v
+}
+
+pub fn path(p: &str) -> SPPath {
+    SPPath::from_string(p)
+}
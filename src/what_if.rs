@@ -0,0 +1,227 @@
+use fuzzy_matcher::FuzzyMatcher;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use iced::{button, scrollable, text_input, Alignment, Button, Column, Element, Length, Row, Scrollable,
+           Text, TextInput};
+use sp_domain::*;
+use std::collections::{HashMap, HashSet};
+use crate::components::{model_transitions, parse_value, view_transition_plan};
+use crate::planning::Plan;
+use crate::predicate_editor::{variables_of, PredicateEdit, PredicateEditor};
+use crate::Message;
+
+#[derive(Debug, Clone)]
+pub enum WhatIfEdit {
+    Goal(PredicateEdit),
+    CopyState,
+    UseLiveState,
+    StateValue(SPPath, String),
+    Plan,
+    ToggleTransition(String),
+}
+
+/// A value in the edited copy of the state.
+#[derive(Debug)]
+struct StartValue {
+    path: SPPath,
+    value: SPValue,
+    edit: String,
+    edit_state: text_input::State,
+}
+
+/// Plans locally from the live state or an edited copy of it, without
+/// sending anything to the runner.
+#[derive(Debug)]
+pub struct WhatIfPlanner {
+    goal: PredicateEditor,
+    variables: HashMap<SPPath, Variable>,
+    variable_names: Vec<String>,
+    transitions: Vec<Transition>,
+    /// None plans from the live state
+    start: Option<Vec<StartValue>>,
+    /// the plan and the state it was computed from
+    pub result: Option<(Result<Plan, String>, SPState)>,
+    pub planning: bool,
+
+    live_button: button::State,
+    copy_button: button::State,
+    plan_button: button::State,
+    plan_buttons: HashMap<String, button::State>,
+    expanded: HashSet<String>,
+    start_scroll: scrollable::State,
+    scroll: scrollable::State,
+}
+
+impl WhatIfPlanner {
+    pub fn new(model: &Model) -> Self {
+        let (variables, variable_names) = variables_of(model);
+        WhatIfPlanner {
+            goal: PredicateEditor::new("AND"),
+            variables,
+            variable_names,
+            transitions: model_transitions(model),
+            start: None,
+            result: None,
+            planning: false,
+            live_button: button::State::new(),
+            copy_button: button::State::new(),
+            plan_button: button::State::new(),
+            plan_buttons: HashMap::new(),
+            expanded: HashSet::new(),
+            start_scroll: scrollable::State::new(),
+            scroll: scrollable::State::new(),
+        }
+    }
+
+    /// Resolve the goal and start state against a reloaded model,
+    /// keeping the edits. The old plan is for the old model, so it goes.
+    pub fn set_model(&mut self, model: &Model) {
+        let (variables, variable_names) = variables_of(model);
+        self.variables = variables;
+        self.variable_names = variable_names;
+        self.transitions = model_transitions(model);
+        self.result = None;
+        self.expanded.clear();
+    }
+
+    pub fn goal(&self) -> Result<Predicate, String> {
+        self.goal.build(&self.variables)
+    }
+
+    /// The state to plan from, with the edits applied if we use a copy.
+    pub fn start_state(&self, live: SPState) -> Result<SPState, String> {
+        let start = match &self.start {
+            Some(start) => start,
+            None => return Ok(live),
+        };
+        let mut values = vec![];
+        for sv in start {
+            let value = if sv.edit.is_empty() {
+                sv.value.clone()
+            } else {
+                let (value_type, domain) = match self.variables.get(&sv.path) {
+                    Some(v) => (v.value_type, v.domain.as_slice()),
+                    None => (sv.value.has_type(), &[][..]),
+                };
+                parse_value(&sv.edit, value_type, domain).map_err(|e| format!("{}: {}", sv.path, e))?
+            };
+            values.push((sv.path.clone(), value));
+        }
+        Ok(SPState::new_from_values(&values))
+    }
+
+    /// Start editing a copy of `live`.
+    pub fn copy_state(&mut self, live: &SPState) {
+        let mut start: Vec<_> = live.projection().state
+            .iter()
+            .map(|(p, v)| StartValue {
+                path: (*p).clone(),
+                value: v.value().clone(),
+                edit: String::new(),
+                edit_state: text_input::State::new(),
+            })
+            .collect();
+        start.sort_by(|a, b| a.path.cmp(&b.path));
+        self.start = Some(start);
+    }
+
+    pub fn update(&mut self, edit: WhatIfEdit) {
+        match edit {
+            WhatIfEdit::Goal(edit) => self.goal.update(edit),
+            WhatIfEdit::UseLiveState => self.start = None,
+            WhatIfEdit::StateValue(path, value) => {
+                if let Some(sv) = self.start.iter_mut().flatten().find(|sv| sv.path == path) {
+                    sv.edit = value;
+                }
+            },
+            WhatIfEdit::ToggleTransition(path) => {
+                if !self.expanded.remove(&path) {
+                    self.expanded.insert(path);
+                }
+            },
+            // these need the live state, see main.
+            WhatIfEdit::CopyState | WhatIfEdit::Plan => (),
+        }
+    }
+
+    pub fn view(&mut self, filter: &str) -> Element<Message> {
+        let matcher = SkimMatcherV2::default().ignore_case();
+
+        let source = Row::new()
+            .spacing(10)
+            .align_items(Alignment::Center)
+            .push(Text::new("Plan from").size(20))
+            .push(Button::new(&mut self.live_button, Text::new("Live state"))
+                  .on_press(Message::WhatIf(WhatIfEdit::UseLiveState)))
+            .push(Button::new(&mut self.copy_button, Text::new("Copy of current state"))
+                  .on_press(Message::WhatIf(WhatIfEdit::CopyState)));
+
+        let start: Element<Message> = match self.start.as_mut() {
+            None => Text::new("the live state").size(14).into(),
+            Some(start) => {
+                let rows = start
+                    .iter_mut()
+                    .filter(|sv| matcher.fuzzy_match(&sv.path.to_string(), filter).is_some())
+                    .fold(Column::new().spacing(5), |col, sv| {
+                        let path = sv.path.clone();
+                        col.push(Row::new()
+                                 .spacing(10)
+                                 .align_items(Alignment::Center)
+                                 .push(Text::new(sv.path.to_string()).size(14).width(Length::FillPortion(3)))
+                                 .push(Text::new(sv.value.to_string()).size(14).width(Length::FillPortion(1)))
+                                 .push(TextInput::new(&mut sv.edit_state, "", &sv.edit,
+                                                      move |v| Message::WhatIf(WhatIfEdit::StateValue(path.clone(), v)))
+                                       .padding(3)
+                                       .width(Length::FillPortion(1))))
+                    });
+                Scrollable::new(&mut self.start_scroll)
+                    .height(Length::Units(150))
+                    .push(rows)
+                    .into()
+            },
+        };
+
+        let goal = self.goal();
+        let plan_button = Button::new(&mut self.plan_button, Text::new("Plan"))
+            .padding(8)
+            .style(crate::style::Button::Primary);
+        let plan_button = if goal.is_ok() && !self.planning {
+            plan_button.on_press(Message::WhatIf(WhatIfEdit::Plan))
+        } else {
+            plan_button
+        };
+        let goal_text = match &goal {
+            Ok(p) => Text::new(format!("goal: {}", p)).size(14),
+            Err(e) => Text::new(e.clone()).size(14).color([0.8, 0.1, 0.1]),
+        };
+
+        let result: Element<Message> = match &self.result {
+            _ if self.planning => Text::new("Planning...").size(20).into(),
+            None => Column::new().into(),
+            Some((Err(e), _)) => Text::new(e.clone()).size(16).color([0.8, 0.1, 0.1]).into(),
+            Some((Ok(plan), _)) if !plan.found => Text::new(plan.summary()).size(20).into(),
+            Some((Ok(plan), from)) => {
+                let paths: Vec<String> = plan.steps.iter().map(|s| s.transition.to_string()).collect();
+                let states = plan.states_before(from);
+                view_transition_plan(plan.summary(), &paths, 0, &self.transitions, &states, filter,
+                                     &mut self.plan_buttons, &self.expanded,
+                                     |p| Message::WhatIf(WhatIfEdit::ToggleTransition(p)))
+            },
+        };
+
+        let content = Column::new()
+            .spacing(10)
+            .push(source)
+            .push(start)
+            .push(Text::new("Goal").size(20))
+            .push(self.goal.view(|e| Message::WhatIf(WhatIfEdit::Goal(e)),
+                                 &self.variable_names, &self.variables))
+            .push(goal_text)
+            .push(plan_button)
+            .push(result);
+
+        Scrollable::new(&mut self.scroll)
+            .height(Length::Fill)
+            .push(content)
+            .into()
+    }
+}